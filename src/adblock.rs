@@ -0,0 +1,688 @@
+use gtk4::glib::ControlFlow;
+use gtk4::{gio, glib, prelude::*, ToggleButton};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::rc::Rc;
+use url::Url;
+use webkit6::prelude::*;
+use webkit6::{LoadEvent, UserContentFilter, UserContentFilterStore, WebView};
+
+use crate::files::{load_json, save_json};
+use crate::listeners::Listeners;
+use crate::tab::tabs;
+use crate::paths::{cache_path, config_path};
+
+const CONTENT_BLOCKER_FILE: &str = "content_blocker.json";
+/// Adblock Plus / EasyList `.txt` filter lists are read from here.
+const FILTERS_DIR: &str = "filters";
+/// WebKit's compiled filters are cached here between runs.
+const FILTER_STORE_DIR: &str = "content-filters";
+const FILTER_PREFIX: &str = "rubra-";
+// WebKit rejects content blockers with too many rules, so large lists are
+// compiled as several filters
+const MAX_RULES_PER_FILTER: usize = 50_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContentBlockerConfig {
+    pub enabled: bool,
+    /// Hosts where nothing is blocked; subdomains are included.
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+}
+
+impl Default for ContentBlockerConfig {
+    fn default() -> Self {
+        ContentBlockerConfig {
+            enabled: true,
+            allowlist: Vec::new(),
+        }
+    }
+}
+
+impl ContentBlockerConfig {
+    pub fn is_allowed(&self, host: &str) -> bool {
+        self.allowlist
+            .iter()
+            .any(|site| host == site || host.ends_with(&format!(".{}", site)))
+    }
+}
+
+fn load_content_blocker_config() -> ContentBlockerConfig {
+    let path = config_path(CONTENT_BLOCKER_FILE);
+    if !path.exists() {
+        let config = ContentBlockerConfig::default();
+        save_content_blocker_config(&config);
+        return config;
+    }
+
+    load_json(&path, "Content blocker settings").unwrap_or_default()
+}
+
+fn save_content_blocker_config(config: &ContentBlockerConfig) {
+    save_json(&config_path(CONTENT_BLOCKER_FILE), config);
+}
+
+/// Content blocker rules converted from an Adblock Plus filter list: block
+/// and element hiding rules, and exceptions which must come after them.
+#[derive(Debug, Default)]
+pub struct ConvertedRules {
+    pub block: Vec<Value>,
+    pub exceptions: Vec<Value>,
+}
+
+// WebKit resource types a filter applies to by default, for negated type
+// options; like Adblock Plus, popups have to be asked for
+const RESOURCE_TYPES: &[&str] = &[
+    "document", "image", "style-sheet", "script", "font", "raw", "svg-document", "media", "ping", "other",
+];
+
+fn resource_type(option: &str) -> Option<&'static str> {
+    Some(match option {
+        "script" => "script",
+        "image" => "image",
+        "stylesheet" => "style-sheet",
+        "font" => "font",
+        "media" => "media",
+        "popup" => "popup",
+        "ping" => "ping",
+        "document" | "subdocument" => "document",
+        "xmlhttprequest" | "websocket" | "webrtc" => "raw",
+        "object" | "other" => "other",
+        _ => return None,
+    })
+}
+
+fn domain_lists(domains: &str, separator: char) -> (Vec<String>, Vec<String>) {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+
+    for domain in domains.split(separator).map(str::trim).filter(|d| !d.is_empty()) {
+        match domain.strip_prefix('~') {
+            Some(domain) => excluded.push(format!("*{}", domain.to_lowercase())),
+            None => included.push(format!("*{}", domain.to_lowercase())),
+        }
+    }
+
+    (included, excluded)
+}
+
+// Adds `if-domain` or `unless-domain`; WebKit allows only one of them, so
+// exclusions are dropped when both are given
+fn add_domains(trigger: &mut Value, included: Vec<String>, excluded: Vec<String>) {
+    if !included.is_empty() {
+        trigger["if-domain"] = json!(included);
+    } else if !excluded.is_empty() {
+        trigger["unless-domain"] = json!(excluded);
+    }
+}
+
+/// Converts the URL pattern of a network filter to the regular expression
+/// subset WebKit understands.
+pub fn pattern_to_regex(pattern: &str) -> Option<String> {
+    if !pattern.is_ascii() {
+        return None;
+    }
+
+    let mut regex = String::new();
+    let mut rest = pattern;
+
+    if let Some(stripped) = rest.strip_prefix("||") {
+        regex.push_str("^[^:]+://+([^:/]+\\.)?");
+        rest = stripped;
+    } else if let Some(stripped) = rest.strip_prefix('|') {
+        regex.push('^');
+        rest = stripped;
+    }
+
+    let anchored_end = rest.ends_with('|');
+    let rest = rest.strip_suffix('|').unwrap_or(rest);
+    // A trailing separator may also be the end of the URL, which WebKit's
+    // regular expressions can't express, so it matches anything there
+    let rest = rest.trim_end_matches('^');
+
+    for c in rest.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '^' => regex.push_str("[^a-zA-Z0-9_.%-]"),
+            '.' | '+' | '?' | '$' | '{' | '}' | '(' | ')' | '[' | ']' | '\\' | '|' => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            _ => regex.push(c),
+        }
+    }
+
+    if anchored_end {
+        regex.push('$');
+    }
+
+    if regex.is_empty() {
+        regex.push_str(".*");
+    }
+
+    Some(regex)
+}
+
+fn convert_network_filter(line: &str) -> Option<(Value, bool)> {
+    let (exception, line) = match line.strip_prefix("@@") {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+
+    let (pattern, options) = match line.rfind('$') {
+        Some(i) if !line[i + 1..].contains('/') => (&line[..i], Some(&line[i + 1..])),
+        _ => (line, None),
+    };
+
+    // Raw regular expressions rarely fit WebKit's subset
+    if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+        return None;
+    }
+
+    let mut trigger = json!({ "url-filter": pattern_to_regex(pattern)? });
+    let mut types: Vec<&str> = Vec::new();
+    let mut excluded_types: Vec<&str> = Vec::new();
+    let mut whole_page = false;
+
+    for option in options.unwrap_or_default().split(',').filter(|o| !o.is_empty()) {
+        let (negated, name) = match option.strip_prefix('~') {
+            Some(name) => (true, name),
+            None => (false, option),
+        };
+
+        match name {
+            "third-party" | "3p" => {
+                trigger["load-type"] = json!([if negated { "first-party" } else { "third-party" }]);
+            }
+            "first-party" | "1p" => {
+                trigger["load-type"] = json!([if negated { "third-party" } else { "first-party" }]);
+            }
+            "match-case" => trigger["url-filter-is-case-sensitive"] = json!(true),
+            "important" => {}
+            _ if name.starts_with("domain=") => {
+                let (included, excluded) = domain_lists(&name["domain=".len()..], '|');
+                add_domains(&mut trigger, included, excluded);
+            }
+            // `@@||site^$document` turns blocking off on a whole site
+            "document" if exception && !negated => whole_page = true,
+            _ => match resource_type(name) {
+                Some(kind) if negated => excluded_types.push(kind),
+                Some(kind) => types.push(kind),
+                // Options we can't express would make the rule too broad
+                None => return None,
+            },
+        }
+    }
+
+    if whole_page {
+        let host = pattern.trim_start_matches("||").trim_end_matches('^');
+        if host.is_empty() || host.contains(['/', '*']) {
+            return None;
+        }
+        let rule = json!({
+            "trigger": { "url-filter": ".*", "if-domain": [format!("*{}", host.to_lowercase())] },
+            "action": { "type": "ignore-previous-rules" },
+        });
+        return Some((rule, true));
+    }
+
+    if types.is_empty() && !excluded_types.is_empty() {
+        types = RESOURCE_TYPES.iter().copied().filter(|t| !excluded_types.contains(t)).collect();
+    }
+    if !types.is_empty() {
+        types.sort();
+        types.dedup();
+        trigger["resource-type"] = json!(types);
+    }
+
+    let action = if exception { "ignore-previous-rules" } else { "block" };
+    Some((json!({ "trigger": trigger, "action": { "type": action } }), exception))
+}
+
+fn convert_hiding_filter(domains: &str, selector: &str) -> Option<Value> {
+    let selector = selector.trim();
+    if selector.is_empty() || !selector.is_ascii() {
+        return None;
+    }
+
+    let mut trigger = json!({ "url-filter": ".*" });
+    let (included, excluded) = domain_lists(domains, ',');
+    add_domains(&mut trigger, included, excluded);
+
+    Some(json!({
+        "trigger": trigger,
+        "action": { "type": "css-display-none", "selector": selector },
+    }))
+}
+
+/// Converts an Adblock Plus / EasyList filter list. Filters WebKit can't
+/// express (snippets, extended selectors, hiding exceptions, rewrites) are
+/// skipped.
+pub fn convert_filter_list(text: &str) -> ConvertedRules {
+    let mut rules = ConvertedRules::default();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            continue;
+        }
+
+        if line.contains("#@#") || line.contains("#?#") || line.contains("#$#") || line.contains("#%#") {
+            continue;
+        }
+
+        if let Some(i) = line.find("##") {
+            if let Some(rule) = convert_hiding_filter(&line[..i], &line[i + 2..]) {
+                rules.block.push(rule);
+            }
+            continue;
+        }
+
+        match convert_network_filter(line) {
+            Some((rule, true)) => rules.exceptions.push(rule),
+            Some((rule, false)) => rules.block.push(rule),
+            None => {}
+        }
+    }
+
+    rules
+}
+
+/// The content blocker rules for every filter list, split into chunks
+/// WebKit can compile. Exceptions follow the blocking rules in every chunk,
+/// since they only override rules before them.
+pub fn build_rule_chunks(lists: &[String]) -> Vec<Vec<Value>> {
+    let mut block = Vec::new();
+    let mut exceptions = Vec::new();
+
+    for list in lists {
+        let rules = convert_filter_list(list);
+        block.extend(rules.block);
+        exceptions.extend(rules.exceptions);
+    }
+
+    block
+        .chunks(MAX_RULES_PER_FILTER)
+        .map(|chunk| chunk.iter().chain(exceptions.iter()).cloned().collect())
+        .collect()
+}
+
+fn read_filter_lists() -> Vec<String> {
+    let Ok(dir) = fs::read_dir(config_path(FILTERS_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<_> = dir
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "txt"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| match fs::read_to_string(path) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                println!("Unable to read filter list {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+thread_local! {
+    static CONFIG: RefCell<ContentBlockerConfig> = RefCell::new(load_content_blocker_config());
+    static FILTERS: RefCell<Vec<UserContentFilter>> = const { RefCell::new(Vec::new()) };
+    static LISTENERS: Listeners = Listeners::default();
+    // Bumped on every reload so results of an older compile are dropped
+    static GENERATION: Cell<u32> = const { Cell::new(0) };
+}
+
+pub fn content_blocker_config() -> ContentBlockerConfig {
+    CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Runs `f` whenever a new set of filters is installed.
+fn connect_filters_changed<F: Fn() -> ControlFlow + 'static>(f: F) {
+    LISTENERS.with(|l| l.connect(f));
+}
+
+fn has_filters() -> bool {
+    FILTERS.with(|f| !f.borrow().is_empty())
+}
+
+/// Turns blocking off or back on for `host` and its subdomains.
+pub fn set_site_allowed(host: &str, allowed: bool) {
+    CONFIG.with(|c| {
+        let mut config = c.borrow_mut();
+        config.allowlist.retain(|site| site != host);
+        if allowed {
+            config.allowlist.push(host.to_string());
+        }
+        save_content_blocker_config(&config);
+    });
+}
+
+fn host_of(webview: &WebView) -> Option<String> {
+    let uri = webview.uri()?;
+    let url = Url::parse(&uri).ok()?;
+    Some(url.host_str()?.trim_start_matches("www.").to_string())
+}
+
+/// Whether content is being blocked on the page shown in `webview`.
+pub fn is_blocking(webview: &WebView) -> bool {
+    let config = content_blocker_config();
+    config.enabled && !host_of(webview).is_some_and(|host| config.is_allowed(&host))
+}
+
+/// Adds the compiled filters to `webview`, or removes them when its site is
+/// allowlisted. Allowlisting per view rather than with rules means the
+/// filters don't have to be compiled again.
+pub fn apply_content_blocker(webview: &WebView) {
+    let Some(manager) = webview.user_content_manager() else {
+        return;
+    };
+
+    manager.remove_all_filters();
+    if !is_blocking(webview) {
+        return;
+    }
+
+    FILTERS.with(|filters| {
+        for filter in filters.borrow().iter() {
+            manager.add_filter(filter);
+        }
+    });
+}
+
+/// Keeps the filters of `webview` in line with the allowlist as it
+/// navigates between sites.
+pub fn connect_content_blocker(webview: &WebView) {
+    apply_content_blocker(webview);
+
+    webview.connect_load_changed(|webview, event| {
+        if matches!(event, LoadEvent::Started | LoadEvent::Redirected) {
+            apply_content_blocker(webview);
+        }
+    });
+}
+
+fn install_filters(filters: Vec<UserContentFilter>) {
+    FILTERS.with(|f| *f.borrow_mut() = filters);
+
+    for tab in tabs() {
+        apply_content_blocker(&tab.webview);
+    }
+    LISTENERS.with(Listeners::notify);
+}
+
+// Loads each chunk from the filter store, compiling the ones not cached yet
+fn load_or_compile(
+    store: UserContentFilterStore,
+    mut pending: Vec<(String, String)>,
+    mut compiled: Vec<UserContentFilter>,
+    generation: u32,
+) {
+    if GENERATION.with(|g| g.get()) != generation {
+        return;
+    }
+
+    let Some((identifier, json)) = pending.pop() else {
+        install_filters(compiled);
+        return;
+    };
+
+    let store_clone = store.clone();
+    store.load(&identifier.clone(), None::<&gio::Cancellable>, move |result| match result {
+        Ok(filter) => {
+            compiled.push(filter);
+            load_or_compile(store_clone, pending, compiled, generation);
+        }
+        Err(_) => {
+            let bytes = glib::Bytes::from_owned(json.into_bytes());
+            let store = store_clone.clone();
+            store_clone.save(&identifier.clone(), &bytes, None::<&gio::Cancellable>, move |result| {
+                match result {
+                    Ok(filter) => compiled.push(filter),
+                    Err(e) => println!("Unable to compile content filter {}: {}", identifier, e),
+                }
+                load_or_compile(store, pending, compiled, generation);
+            });
+        }
+    });
+}
+
+// Removes compiled chunks of older filter lists. The store keeps one
+// "ContentRuleList-<identifier>" file per filter, and webkit6 has no binding
+// for listing its identifiers.
+fn remove_stale_filters(store: &UserContentFilterStore, store_dir: &Path, pending: &[(String, String)]) {
+    let Ok(files) = fs::read_dir(store_dir) else {
+        return;
+    };
+
+    for file in files.flatten() {
+        let name = file.file_name().to_string_lossy().into_owned();
+        let Some(identifier) = name.strip_prefix("ContentRuleList-") else {
+            continue;
+        };
+        if identifier.starts_with(FILTER_PREFIX) && !pending.iter().any(|(id, _)| id == identifier) {
+            store.remove(identifier, None::<&gio::Cancellable>, |_| {});
+        }
+    }
+}
+
+/// Reads the filter lists and (re)compiles the content blocker in the
+/// background, then applies it to every open tab.
+pub fn reload_content_blocker() {
+    let generation = GENERATION.with(|g| {
+        g.set(g.get() + 1);
+        g.get()
+    });
+
+    if !content_blocker_config().enabled {
+        install_filters(Vec::new());
+        return;
+    }
+
+    let lists = read_filter_lists();
+    if lists.is_empty() {
+        println!("No filter lists in {}, content blocking is off", config_path(FILTERS_DIR).display());
+        install_filters(Vec::new());
+        return;
+    }
+
+    // Identified by their content, so unchanged lists aren't compiled again
+    let pending: Vec<(String, String)> = build_rule_chunks(&lists)
+        .iter()
+        .map(|chunk| {
+            let json = serde_json::to_string(chunk).expect("Failed to serialize content filter");
+            let mut hasher = DefaultHasher::new();
+            json.hash(&mut hasher);
+            (format!("{}{:016x}", FILTER_PREFIX, hasher.finish()), json)
+        })
+        .collect();
+
+    let store_dir = cache_path(FILTER_STORE_DIR);
+    let store = UserContentFilterStore::new(store_dir.to_str().expect("Invalid filter store path"));
+    remove_stale_filters(&store, &store_dir, &pending);
+
+    load_or_compile(store, pending, Vec::new(), generation);
+}
+
+fn update_blocker_button(button: &ToggleButton, webview: &WebView) {
+    match host_of(webview) {
+        // Nothing would be blocked, so toggling it would only mislead
+        _ if !has_filters() => {
+            button.set_sensitive(false);
+            button.set_active(false);
+            button.set_tooltip_text(Some(&format!(
+                "No filter lists, add some to {} to block ads and trackers",
+                config_path(FILTERS_DIR).display()
+            )));
+        }
+        Some(host) if content_blocker_config().enabled => {
+            let blocking = is_blocking(webview);
+            button.set_sensitive(true);
+            button.set_active(blocking);
+            button.set_tooltip_text(Some(&if blocking {
+                format!("Blocking ads and trackers on {}", host)
+            } else {
+                format!("Content blocking is off on {}", host)
+            }));
+        }
+        _ => {
+            button.set_sensitive(false);
+            button.set_active(false);
+            button.set_tooltip_text(Some("Content blocking is off"));
+        }
+    }
+}
+
+/// Toolbar toggle that turns content blocking off and on for the site in
+/// `webview`.
+pub fn create_blocker_button(webview: &WebView) -> ToggleButton {
+    let button = ToggleButton::with_label("🛡");
+    button.add_css_class("flat");
+    update_blocker_button(&button, webview);
+
+    // Set while the button is updated to match the page
+    let updating = Rc::new(Cell::new(false));
+
+    let button_clone = button.clone();
+    let updating_clone = updating.clone();
+    webview.connect_notify_local(Some("uri"), move |webview, _| {
+        updating_clone.set(true);
+        update_blocker_button(&button_clone, webview);
+        updating_clone.set(false);
+    });
+
+    // The filters are compiled in the background and may come after the tab
+    let button_weak = button.downgrade();
+    let webview_weak = webview.downgrade();
+    let updating_clone = updating.clone();
+    connect_filters_changed(move || {
+        let (Some(button), Some(webview)) = (button_weak.upgrade(), webview_weak.upgrade()) else {
+            return ControlFlow::Break;
+        };
+        updating_clone.set(true);
+        update_blocker_button(&button, &webview);
+        updating_clone.set(false);
+        ControlFlow::Continue
+    });
+
+    let webview_clone = webview.clone();
+    button.connect_toggled(move |button| {
+        if updating.get() {
+            return;
+        }
+        let Some(host) = host_of(&webview_clone) else {
+            return;
+        };
+
+        set_site_allowed(&host, !button.is_active());
+        apply_content_blocker(&webview_clone);
+        webview_clone.reload();
+    });
+
+    button
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn matches(pattern: &str, url: &str) -> bool {
+        Regex::new(&pattern_to_regex(pattern).unwrap()).unwrap().is_match(url)
+    }
+
+    #[test]
+    fn domain_anchors_match_the_domain_and_subdomains() {
+        assert_eq!(pattern_to_regex("||ads.example.com^").unwrap(), "^[^:]+://+([^:/]+\\.)?ads\\.example\\.com");
+        assert!(matches("||example.com^", "https://example.com/ad.js"));
+        assert!(matches("||example.com^", "http://cdn.example.com/"));
+        assert!(!matches("||example.com^", "https://notexample.com/"));
+        assert!(!matches("||example.com^", "https://other.org/?u=example.com"));
+    }
+
+    #[test]
+    fn start_and_end_anchors() {
+        assert_eq!(pattern_to_regex("|https://").unwrap(), "^https://");
+        assert_eq!(pattern_to_regex(".swf|").unwrap(), "\\.swf$");
+        assert!(matches("|https://ads.", "https://ads.example.com/"));
+        assert!(!matches("|https://ads.", "https://example.com/?https://ads."));
+        assert!(matches(".swf|", "https://example.com/movie.swf"));
+        assert!(!matches(".swf|", "https://example.com/movie.swf?x=1"));
+    }
+
+    #[test]
+    fn wildcards_separators_and_escapes() {
+        assert_eq!(pattern_to_regex("/ads/*/banner^").unwrap(), "/ads/.*/banner");
+        assert_eq!(pattern_to_regex("/ad^x?(1)").unwrap(), "/ad[^a-zA-Z0-9_.%-]x\\?\\(1\\)");
+        assert_eq!(pattern_to_regex("").unwrap(), ".*");
+        assert_eq!(pattern_to_regex("/ädvert/"), None);
+    }
+
+    #[test]
+    fn converts_options() {
+        let rules = convert_filter_list("||tracker.com^$third-party,script\n||ads.net^$domain=a.com|~b.a.com");
+
+        assert_eq!(rules.block.len(), 2);
+        assert!(rules.exceptions.is_empty());
+        assert_eq!(rules.block[0]["trigger"]["load-type"], json!(["third-party"]));
+        assert_eq!(rules.block[0]["trigger"]["resource-type"], json!(["script"]));
+        assert_eq!(rules.block[0]["action"]["type"], "block");
+        assert_eq!(rules.block[1]["trigger"]["if-domain"], json!(["*a.com"]));
+        assert!(rules.block[1]["trigger"].get("unless-domain").is_none());
+
+        let rules = convert_filter_list("||ads.net^$domain=~b.com,~third-party");
+        assert_eq!(rules.block[0]["trigger"]["unless-domain"], json!(["*b.com"]));
+        assert_eq!(rules.block[0]["trigger"]["load-type"], json!(["first-party"]));
+    }
+
+    #[test]
+    fn converts_exceptions() {
+        let rules = convert_filter_list("@@||example.com/ads.js\n@@||example.org^$document");
+
+        assert!(rules.block.is_empty());
+        assert_eq!(rules.exceptions.len(), 2);
+        assert_eq!(rules.exceptions[0]["action"]["type"], "ignore-previous-rules");
+        assert_eq!(rules.exceptions[1]["trigger"], json!({ "url-filter": ".*", "if-domain": ["*example.org"] }));
+    }
+
+    #[test]
+    fn converts_hiding_filters_and_skips_the_rest() {
+        let list = "! comment\n[Adblock Plus 2.0]\n\nexample.com,~shop.example.com##.ad\n##.banner\n\
+                    example.com#@#.ad\nexample.com#?#div:-abp-has(.ad)\n\
+                    /banner[0-9]+/\n||ads.com^$rewrite=abp-resource:x";
+        let rules = convert_filter_list(list);
+
+        assert_eq!(rules.block.len(), 2);
+        assert_eq!(rules.block[0]["action"], json!({ "type": "css-display-none", "selector": ".ad" }));
+        assert_eq!(rules.block[0]["trigger"]["if-domain"], json!(["*example.com"]));
+        assert_eq!(rules.block[1]["trigger"], json!({ "url-filter": ".*" }));
+    }
+
+    #[test]
+    fn exceptions_follow_the_blocking_rules_of_every_chunk() {
+        let mut list: String = (0..MAX_RULES_PER_FILTER).map(|i| format!("||ads{}.com^\n", i)).collect();
+        let exceptions = "@@||good.com^\n";
+
+        let chunks = build_rule_chunks(&[list.clone(), exceptions.to_string()]);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].len(), MAX_RULES_PER_FILTER + 1);
+
+        list.push_str("||one-more.com^\n");
+        let chunks = build_rule_chunks(&[list, exceptions.to_string()]);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), MAX_RULES_PER_FILTER + 1);
+        assert_eq!(chunks[1].len(), 2);
+        for chunk in &chunks {
+            assert_eq!(chunk.last().unwrap()["action"]["type"], "ignore-previous-rules");
+        }
+    }
+}
@@ -0,0 +1,941 @@
+use gtk4::glib::{self, ControlFlow};
+use gtk4::pango::EllipsizeMode;
+use gtk4::{
+    gdk, gio, prelude::*, AlertDialog, Application, ApplicationWindow, Box, Button, DropDown, Entry, FileDialog, Image,
+    Label, ListBox, ListBoxRow, MenuButton, Orientation, Popover, ScrolledWindow, Switch,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use webkit6::prelude::*;
+use webkit6::WebView;
+
+use crate::bookmarks_io::{export_netscape, import_file};
+use crate::files::{load_json, save_json};
+use crate::history::now;
+use crate::paths::data_path;
+use crate::listeners::Listeners;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bookmark {
+    pub id: u64,
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// PNG favicon as a `data:` URI.
+    #[serde(default)]
+    pub favicon: Option<String>,
+    pub created: i64,
+    pub modified: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Folder {
+    pub id: u64,
+    pub title: String,
+    pub created: i64,
+    pub modified: i64,
+    pub children: Vec<Node>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Bookmark(Bookmark),
+    Folder(Folder),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bookmarks {
+    next_id: u64,
+    #[serde(default)]
+    pub show_bar: bool,
+    /// Shown in the bookmarks bar.
+    pub bar: Folder,
+    pub other: Folder,
+}
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+pub const BAR_ID: u64 = 1;
+pub const OTHER_ID: u64 = 2;
+
+impl Node {
+    pub fn id(&self) -> u64 {
+        match self {
+            Node::Bookmark(b) => b.id,
+            Node::Folder(f) => f.id,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        match self {
+            Node::Bookmark(b) => &b.title,
+            Node::Folder(f) => &f.title,
+        }
+    }
+}
+
+impl Folder {
+    pub fn new(id: u64, title: &str) -> Folder {
+        let time = now();
+        Folder {
+            id,
+            title: title.to_string(),
+            created: time,
+            modified: time,
+            children: Vec::new(),
+        }
+    }
+
+    fn folder(&self, id: u64) -> Option<&Folder> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| match child {
+            Node::Folder(f) => f.folder(id),
+            Node::Bookmark(_) => None,
+        })
+    }
+
+    fn folder_mut(&mut self, id: u64) -> Option<&mut Folder> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|child| match child {
+            Node::Folder(f) => f.folder_mut(id),
+            Node::Bookmark(_) => None,
+        })
+    }
+
+    fn node(&self, id: u64) -> Option<&Node> {
+        for child in &self.children {
+            if child.id() == id {
+                return Some(child);
+            }
+            if let Node::Folder(f) = child {
+                if let Some(found) = f.node(id) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    fn bookmark_mut(&mut self, id: u64) -> Option<&mut Bookmark> {
+        self.children.iter_mut().find_map(|child| match child {
+            Node::Bookmark(b) if b.id == id => Some(b),
+            Node::Bookmark(_) => None,
+            Node::Folder(f) => f.bookmark_mut(id),
+        })
+    }
+
+    fn remove(&mut self, id: u64) -> Option<Node> {
+        if let Some(index) = self.children.iter().position(|c| c.id() == id) {
+            self.modified = now();
+            return Some(self.children.remove(index));
+        }
+        self.children.iter_mut().find_map(|child| match child {
+            Node::Folder(f) => f.remove(id),
+            Node::Bookmark(_) => None,
+        })
+    }
+
+    pub fn bookmarks<'a>(&'a self, out: &mut Vec<&'a Bookmark>) {
+        for child in &self.children {
+            match child {
+                Node::Bookmark(b) => out.push(b),
+                Node::Folder(f) => f.bookmarks(out),
+            }
+        }
+    }
+
+    fn bookmarks_mut(&mut self, f: &mut dyn FnMut(&mut Bookmark)) {
+        for child in &mut self.children {
+            match child {
+                Node::Bookmark(b) => f(b),
+                Node::Folder(folder) => folder.bookmarks_mut(f),
+            }
+        }
+    }
+
+    fn folders(&self, depth: usize, out: &mut Vec<(u64, String, usize)>) {
+        out.push((self.id, self.title.clone(), depth));
+        for child in &self.children {
+            if let Node::Folder(f) = child {
+                f.folders(depth + 1, out);
+            }
+        }
+    }
+}
+
+impl Bookmarks {
+    pub fn new() -> Bookmarks {
+        Bookmarks {
+            next_id: OTHER_ID + 1,
+            show_bar: true,
+            bar: Folder::new(BAR_ID, "Bookmarks Bar"),
+            other: Folder::new(OTHER_ID, "Other Bookmarks"),
+        }
+    }
+
+    fn take_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn folder(&self, id: u64) -> Option<&Folder> {
+        self.bar.folder(id).or_else(|| self.other.folder(id))
+    }
+
+    pub fn folder_mut(&mut self, id: u64) -> Option<&mut Folder> {
+        if self.bar.folder(id).is_some() {
+            self.bar.folder_mut(id)
+        } else {
+            self.other.folder_mut(id)
+        }
+    }
+
+    pub fn node(&self, id: u64) -> Option<&Node> {
+        self.bar.node(id).or_else(|| self.other.node(id))
+    }
+
+    /// Every bookmark, in tree order.
+    pub fn all_bookmarks(&self) -> Vec<&Bookmark> {
+        let mut out = Vec::new();
+        self.bar.bookmarks(&mut out);
+        self.other.bookmarks(&mut out);
+        out
+    }
+
+    /// Every folder with its nesting depth, in tree order.
+    pub fn folders(&self) -> Vec<(u64, String, usize)> {
+        let mut out = Vec::new();
+        self.bar.folders(0, &mut out);
+        self.other.folders(0, &mut out);
+        out
+    }
+
+    pub fn find_by_url(&self, url: &str) -> Option<&Bookmark> {
+        self.all_bookmarks().into_iter().find(|b| b.url == url)
+    }
+
+    fn insert(&mut self, parent: u64, index: Option<usize>, node: Node) -> Option<u64> {
+        let id = node.id();
+        let folder = self.folder_mut(parent)?;
+        let index = index.unwrap_or(folder.children.len()).min(folder.children.len());
+        folder.children.insert(index, node);
+        folder.modified = now();
+        Some(id)
+    }
+
+    pub fn add_bookmark(&mut self, parent: u64, title: &str, url: &str, tags: Vec<String>) -> Option<u64> {
+        let time = now();
+        let bookmark = Bookmark {
+            id: self.take_id(),
+            title: title.to_string(),
+            url: url.to_string(),
+            tags,
+            favicon: None,
+            created: time,
+            modified: time,
+        };
+        self.insert(parent, None, Node::Bookmark(bookmark))
+    }
+
+    pub fn add_folder(&mut self, parent: u64, title: &str) -> Option<u64> {
+        let folder = Folder::new(self.take_id(), title);
+        self.insert(parent, None, Node::Folder(folder))
+    }
+
+    fn assign_ids(&mut self, node: &mut Node) {
+        match node {
+            Node::Bookmark(b) => b.id = self.take_id(),
+            Node::Folder(f) => {
+                f.id = self.take_id();
+                for child in &mut f.children {
+                    self.assign_ids(child);
+                }
+            }
+        }
+    }
+
+    /// Appends nodes built outside the store, such as imported ones, to the
+    /// folder `parent`, giving them fresh ids.
+    pub fn insert_tree(&mut self, parent: u64, nodes: Vec<Node>) {
+        for mut node in nodes {
+            self.assign_ids(&mut node);
+            self.insert(parent, None, node);
+        }
+    }
+
+    pub fn update_bookmark(&mut self, id: u64, title: &str, url: &str, tags: Vec<String>) {
+        let bookmark = self.bar.bookmark_mut(id).or_else(|| self.other.bookmark_mut(id));
+        if let Some(bookmark) = bookmark {
+            bookmark.title = title.to_string();
+            bookmark.url = url.to_string();
+            bookmark.tags = tags;
+            bookmark.modified = now();
+        }
+    }
+
+    pub fn rename_folder(&mut self, id: u64, title: &str) {
+        if let Some(folder) = self.folder_mut(id) {
+            folder.title = title.to_string();
+            folder.modified = now();
+        }
+    }
+
+    /// Sets the favicon of every bookmark of `url`, returns whether anything
+    /// changed.
+    pub fn set_favicon(&mut self, url: &str, favicon: &str) -> bool {
+        let mut changed = false;
+        let mut update = |b: &mut Bookmark| {
+            if b.url == url && b.favicon.as_deref() != Some(favicon) {
+                b.favicon = Some(favicon.to_string());
+                changed = true;
+            }
+        };
+        self.bar.bookmarks_mut(&mut update);
+        self.other.bookmarks_mut(&mut update);
+        changed
+    }
+
+    /// Removes a bookmark or folder. The two root folders can't be removed.
+    pub fn remove(&mut self, id: u64) -> Option<Node> {
+        if id == BAR_ID || id == OTHER_ID {
+            return None;
+        }
+        self.bar.remove(id).or_else(|| self.other.remove(id))
+    }
+
+    /// Moves a bookmark or folder to `index` in the folder `parent`.
+    pub fn move_node(&mut self, id: u64, parent: u64, index: usize) -> bool {
+        let into_itself = match self.node(id) {
+            Some(Node::Folder(f)) => f.folder(parent).is_some(),
+            Some(Node::Bookmark(_)) => false,
+            None => return false,
+        };
+        if into_itself || self.folder(parent).is_none() {
+            return false;
+        }
+
+        match self.remove(id) {
+            Some(node) => self.insert(parent, Some(index), node).is_some(),
+            None => false,
+        }
+    }
+}
+
+thread_local! {
+    static BOOKMARKS: Rc<RefCell<Bookmarks>> = Rc::new(RefCell::new(load_bookmarks()));
+    static LISTENERS: Listeners = Listeners::default();
+}
+
+fn load_bookmarks() -> Bookmarks {
+    let path = data_path(BOOKMARKS_FILE);
+    if path.exists() {
+        load_json(&path, "Bookmarks").unwrap_or_else(Bookmarks::new)
+    } else {
+        let bookmarks = Bookmarks::new();
+        save_bookmarks(&bookmarks);
+        bookmarks
+    }
+}
+
+fn save_bookmarks(bookmarks: &Bookmarks) {
+    save_json(&data_path(BOOKMARKS_FILE), bookmarks);
+}
+
+/// The bookmarks store shared by every window.
+pub fn bookmarks() -> Rc<RefCell<Bookmarks>> {
+    BOOKMARKS.with(Rc::clone)
+}
+
+/// Calls `f` after every change to the bookmarks until it returns
+/// `ControlFlow::Break`.
+pub fn connect_bookmarks_changed<F: Fn() -> ControlFlow + 'static>(f: F) {
+    LISTENERS.with(|l| l.connect(f));
+}
+
+/// Saves the bookmarks and notifies listeners. Call after modifying
+/// `bookmarks()`, with the borrow released.
+pub fn bookmarks_changed() {
+    save_bookmarks(&bookmarks().borrow());
+
+    LISTENERS.with(Listeners::notify);
+}
+
+pub fn favicon_data_uri(texture: &gdk::Texture) -> String {
+    format!("data:image/png;base64,{}", glib::base64_encode(&texture.save_to_png_bytes()))
+}
+
+fn favicon_image(favicon: Option<&str>) -> Image {
+    let texture = favicon
+        .and_then(|uri| uri.strip_prefix("data:image/png;base64,"))
+        .and_then(|data| gdk::Texture::from_bytes(&glib::Bytes::from_owned(glib::base64_decode(data))).ok());
+
+    match texture {
+        Some(texture) => Image::from_paintable(Some(&texture)),
+        None => Image::from_icon_name("text-html-symbolic"),
+    }
+}
+
+pub fn parse_tags(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Star button for the tab's top bar, filled while the page is bookmarked.
+pub fn create_star_button(webview: &WebView) -> Button {
+    let star = Button::with_label("☆");
+
+    let update = {
+        let star = star.downgrade();
+        let webview = webview.downgrade();
+        move || {
+            let (Some(star), Some(webview)) = (star.upgrade(), webview.upgrade()) else {
+                return ControlFlow::Break;
+            };
+            let bookmarked = webview
+                .uri()
+                .is_some_and(|uri| bookmarks().borrow().find_by_url(&uri).is_some());
+            star.set_label(if bookmarked { "★" } else { "☆" });
+            ControlFlow::Continue
+        }
+    };
+    update();
+
+    let update_clone = update.clone();
+    webview.connect_notify_local(Some("uri"), move |_, _| {
+        update_clone();
+    });
+    connect_bookmarks_changed(update);
+
+    let webview_clone = webview.clone();
+    star.connect_clicked(move |_| {
+        let Some(uri) = webview_clone.uri() else {
+            return;
+        };
+
+        let existing = bookmarks().borrow().find_by_url(&uri).map(|b| b.id);
+        match existing {
+            Some(id) => {
+                bookmarks().borrow_mut().remove(id);
+            }
+            None => {
+                let title = webview_clone.title().map(|t| t.to_string()).unwrap_or_else(|| uri.to_string());
+                let store = bookmarks();
+                let mut store = store.borrow_mut();
+                store.add_bookmark(OTHER_ID, &title, &uri, Vec::new());
+                if let Some(texture) = webview_clone.favicon() {
+                    store.set_favicon(&uri, &favicon_data_uri(&texture));
+                }
+            }
+        }
+        bookmarks_changed();
+    });
+
+    // Favicons usually arrive after the page was bookmarked
+    webview.connect_notify_local(Some("favicon"), |webview, _| {
+        if let (Some(uri), Some(texture)) = (webview.uri(), webview.favicon()) {
+            if bookmarks().borrow().find_by_url(&uri).is_none() {
+                return;
+            }
+            let changed = bookmarks().borrow_mut().set_favicon(&uri, &favicon_data_uri(&texture));
+            if changed {
+                bookmarks_changed();
+            }
+        }
+    });
+
+    star
+}
+
+fn bookmark_button(bookmark: &Bookmark, webview: &WebView, popover: Option<&Popover>) -> Button {
+    let hbox = Box::new(Orientation::Horizontal, 5);
+    hbox.append(&favicon_image(bookmark.favicon.as_deref()));
+
+    let label = Label::new(Some(&bookmark.title));
+    label.set_ellipsize(EllipsizeMode::End);
+    label.set_max_width_chars(20);
+    hbox.append(&label);
+
+    let button = Button::new();
+    button.set_child(Some(&hbox));
+    button.set_tooltip_text(Some(&bookmark.url));
+    button.add_css_class("flat");
+
+    let url = bookmark.url.clone();
+    let webview = webview.clone();
+    let popover = popover.cloned();
+    button.connect_clicked(move |_| {
+        if let Some(popover) = &popover {
+            popover.popdown();
+        }
+        webview.load_uri(&url);
+    });
+
+    button
+}
+
+fn folder_button(folder: &Folder, webview: &WebView) -> MenuButton {
+    let menu = MenuButton::new();
+    menu.set_label(&format!("🗀 {}", folder.title));
+    menu.add_css_class("flat");
+
+    let popover = Popover::new();
+    let vbox = Box::new(Orientation::Vertical, 0);
+
+    for child in &folder.children {
+        match child {
+            Node::Bookmark(b) => vbox.append(&bookmark_button(b, webview, Some(&popover))),
+            Node::Folder(f) => vbox.append(&folder_button(f, webview)),
+        }
+    }
+
+    if folder.children.is_empty() {
+        vbox.append(&Label::new(Some("(empty)")));
+    }
+
+    popover.set_child(Some(&vbox));
+    menu.set_popover(Some(&popover));
+    menu
+}
+
+/// The bookmarks bar shown under a tab's top bar, kept in sync with the
+/// store.
+pub fn create_bookmarks_bar(webview: &WebView) -> Box {
+    let bar = Box::new(Orientation::Horizontal, 0);
+
+    let rebuild = {
+        let bar = bar.downgrade();
+        let webview = webview.downgrade();
+        move || {
+            let (Some(bar), Some(webview)) = (bar.upgrade(), webview.upgrade()) else {
+                return ControlFlow::Break;
+            };
+
+            while let Some(child) = bar.first_child() {
+                bar.remove(&child);
+            }
+
+            let store = bookmarks();
+            let store = store.borrow();
+            for child in &store.bar.children {
+                match child {
+                    Node::Bookmark(b) => bar.append(&bookmark_button(b, &webview, None)),
+                    Node::Folder(f) => bar.append(&folder_button(f, &webview)),
+                }
+            }
+            bar.set_visible(store.show_bar);
+
+            ControlFlow::Continue
+        }
+    };
+    rebuild();
+    connect_bookmarks_changed(rebuild);
+
+    bar
+}
+
+// The folder list and the contents of the open folder in the management
+// window. Opening a folder only redraws the window, the store is unchanged.
+struct Manager {
+    window: glib::WeakRef<ApplicationWindow>,
+    sidebar: ListBox,
+    list: ListBox,
+    selected: Cell<u64>,
+}
+
+impl Manager {
+    fn open_folder(self: &Rc<Self>, id: u64) {
+        self.selected.set(id);
+        self.refresh();
+    }
+
+    fn refresh(self: &Rc<Self>) -> ControlFlow {
+        if self.window.upgrade().is_none() {
+            return ControlFlow::Break;
+        }
+
+        // The selected folder may have been deleted
+        if bookmarks().borrow().folder(self.selected.get()).is_none() {
+            self.selected.set(BAR_ID);
+        }
+
+        while let Some(child) = self.sidebar.first_child() {
+            self.sidebar.remove(&child);
+        }
+        for (id, title, depth) in bookmarks().borrow().folders() {
+            let button = Button::with_label(&format!("{}{}", "    ".repeat(depth), title));
+            button.add_css_class("flat");
+            if id == self.selected.get() {
+                button.add_css_class("suggested-action");
+            }
+            let manager = Rc::downgrade(self);
+            button.connect_clicked(move |_| {
+                if let Some(manager) = manager.upgrade() {
+                    manager.open_folder(id);
+                }
+            });
+            self.sidebar.append(&button);
+        }
+
+        bookmark_rows(&self.list, self.selected.get(), &Rc::downgrade(self));
+
+        ControlFlow::Continue
+    }
+}
+
+// Rows for the items of one folder in the management window
+fn bookmark_rows(list: &ListBox, folder_id: u64, manager: &Weak<Manager>) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+
+    let store = bookmarks();
+    let store = store.borrow();
+    let Some(folder) = store.folder(folder_id) else {
+        return;
+    };
+
+    let folders = store.folders();
+    let folder_names: Vec<String> = folders
+        .iter()
+        .map(|(_, title, depth)| format!("{}{}", "  ".repeat(*depth), title))
+        .collect();
+    let folder_refs: Vec<&str> = folder_names.iter().map(|n| n.as_str()).collect();
+    let folder_ids: Rc<Vec<u64>> = Rc::new(folders.iter().map(|(id, _, _)| *id).collect());
+
+    for (index, child) in folder.children.iter().enumerate() {
+        let row = ListBoxRow::new();
+        let hbox = Box::new(Orientation::Horizontal, 10);
+        let id = child.id();
+
+        let title = Entry::new();
+        title.set_text(child.title());
+        title.set_hexpand(true);
+
+        match child {
+            Node::Bookmark(b) => {
+                hbox.append(&favicon_image(b.favicon.as_deref()));
+                hbox.append(&title);
+
+                let url = Entry::new();
+                url.set_text(&b.url);
+                url.set_hexpand(true);
+                hbox.append(&url);
+
+                let tags = Entry::new();
+                tags.set_placeholder_text(Some("tags, comma separated"));
+                tags.set_text(&b.tags.join(", "));
+                hbox.append(&tags);
+
+                let save = Button::with_label("Save");
+                save.connect_clicked(move |_| {
+                    bookmarks()
+                        .borrow_mut()
+                        .update_bookmark(id, &title.text(), &url.text(), parse_tags(&tags.text()));
+                    bookmarks_changed();
+                });
+                hbox.append(&save);
+            }
+            Node::Folder(_) => {
+                hbox.append(&Label::new(Some("🗀")));
+                hbox.append(&title);
+
+                let rename = Button::with_label("Rename");
+                rename.connect_clicked(move |_| {
+                    bookmarks().borrow_mut().rename_folder(id, &title.text());
+                    bookmarks_changed();
+                });
+                hbox.append(&rename);
+
+                let open = Button::with_label("Open");
+                let manager = manager.clone();
+                open.connect_clicked(move |_| {
+                    if let Some(manager) = manager.upgrade() {
+                        manager.open_folder(id);
+                    }
+                });
+                hbox.append(&open);
+            }
+        }
+
+        let move_to = DropDown::from_strings(&folder_refs);
+        if let Some(position) = folder_ids.iter().position(|f| *f == folder_id) {
+            move_to.set_selected(position as u32);
+        }
+        let folder_ids_clone = Rc::clone(&folder_ids);
+        move_to.connect_selected_notify(move |dropdown| {
+            if let Some(parent) = folder_ids_clone.get(dropdown.selected() as usize) {
+                if *parent != folder_id && bookmarks().borrow_mut().move_node(id, *parent, usize::MAX) {
+                    bookmarks_changed();
+                }
+            }
+        });
+        hbox.append(&move_to);
+
+        let up = Button::with_label("↑");
+        up.set_sensitive(index > 0);
+        up.connect_clicked(move |_| {
+            bookmarks().borrow_mut().move_node(id, folder_id, index.saturating_sub(1));
+            bookmarks_changed();
+        });
+        hbox.append(&up);
+
+        let down = Button::with_label("↓");
+        down.set_sensitive(index + 1 < folder.children.len());
+        down.connect_clicked(move |_| {
+            bookmarks().borrow_mut().move_node(id, folder_id, index + 1);
+            bookmarks_changed();
+        });
+        hbox.append(&down);
+
+        let delete = Button::with_label("Delete");
+        delete.connect_clicked(move |_| {
+            bookmarks().borrow_mut().remove(id);
+            bookmarks_changed();
+        });
+        hbox.append(&delete);
+
+        row.set_child(Some(&hbox));
+        list.append(&row);
+    }
+
+    if folder.children.is_empty() {
+        list.append(&Label::new(Some("This folder is empty")));
+    }
+}
+
+pub fn create_bookmarks_window(application: &Application) {
+    let window = ApplicationWindow::new(application);
+    window.set_title(Some("aapelix/rubra/bookmarks"));
+    window.set_default_size(1100, 600);
+
+    let hbox = Box::new(Orientation::Horizontal, 10);
+    let sidebar = ListBox::new();
+    sidebar.set_vexpand(true);
+
+    let manager = Rc::new(Manager {
+        window: window.downgrade(),
+        sidebar: sidebar.clone(),
+        list: ListBox::new(),
+        selected: Cell::new(BAR_ID),
+    });
+
+    let content = Box::new(Orientation::Vertical, 10);
+    let toolbar = Box::new(Orientation::Horizontal, 10);
+
+    let folder_name = Entry::new();
+    folder_name.set_placeholder_text(Some("New folder name"));
+    toolbar.append(&folder_name);
+
+    let new_folder = Button::with_label("New Folder");
+    let manager_clone = Rc::downgrade(&manager);
+    new_folder.connect_clicked(move |_| {
+        let name = folder_name.text();
+        if name.is_empty() {
+            return;
+        }
+        let Some(manager) = manager_clone.upgrade() else {
+            return;
+        };
+        bookmarks().borrow_mut().add_folder(manager.selected.get(), &name);
+        folder_name.set_text("");
+        bookmarks_changed();
+    });
+    toolbar.append(&new_folder);
+
+    toolbar.append(&Label::new(Some("Show bookmarks bar")));
+    let show_bar = Switch::new();
+    show_bar.set_active(bookmarks().borrow().show_bar);
+    show_bar.connect_state_set(|_, state| {
+        bookmarks().borrow_mut().show_bar = state;
+        bookmarks_changed();
+        glib::Propagation::Proceed
+    });
+    toolbar.append(&show_bar);
+
+    let import = Button::with_label("Import…");
+    let window_clone = window.clone();
+    import.connect_clicked(move |_| {
+        let dialog = FileDialog::new();
+        dialog.set_title("Import bookmarks");
+
+        let window = window_clone.clone();
+        dialog.open(Some(&window_clone), None::<&gio::Cancellable>, move |result| {
+            let Some(path) = result.ok().and_then(|file| file.path()) else {
+                return;
+            };
+
+            match import_file(&path) {
+                Ok(imported) => {
+                    {
+                        let store = bookmarks();
+                        let mut store = store.borrow_mut();
+                        store.insert_tree(BAR_ID, imported.bar);
+                        store.insert_tree(OTHER_ID, imported.other);
+                    }
+                    bookmarks_changed();
+                }
+                Err(e) => {
+                    AlertDialog::builder()
+                        .message("Unable to import bookmarks")
+                        .detail(e)
+                        .build()
+                        .show(Some(&window));
+                }
+            }
+        });
+    });
+    toolbar.append(&import);
+
+    let export = Button::with_label("Export…");
+    let window_clone = window.clone();
+    export.connect_clicked(move |_| {
+        let dialog = FileDialog::new();
+        dialog.set_title("Export bookmarks");
+        dialog.set_initial_name(Some("bookmarks.html"));
+
+        let window = window_clone.clone();
+        dialog.save(Some(&window_clone), None::<&gio::Cancellable>, move |result| {
+            let Some(path) = result.ok().and_then(|file| file.path()) else {
+                return;
+            };
+
+            let html = export_netscape(&bookmarks().borrow());
+            if let Err(e) = std::fs::write(&path, html) {
+                AlertDialog::builder()
+                    .message("Unable to export bookmarks")
+                    .detail(e.to_string())
+                    .build()
+                    .show(Some(&window));
+            }
+        });
+    });
+    toolbar.append(&export);
+
+    let scrolled_window = ScrolledWindow::new();
+    scrolled_window.set_vexpand(true);
+    scrolled_window.set_hexpand(true);
+    scrolled_window.set_child(Some(&manager.list));
+
+    content.append(&toolbar);
+    content.append(&scrolled_window);
+
+    manager.refresh();
+    connect_bookmarks_changed(move || manager.refresh());
+
+    hbox.append(&sidebar);
+    hbox.append(&content);
+
+    window.set_child(Some(&hbox));
+    window.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(folder: &Folder) -> Vec<&str> {
+        folder.children.iter().map(|c| c.title()).collect()
+    }
+
+    // Bar: a, folder (b, inner (c)); Other: d
+    fn sample() -> (Bookmarks, [u64; 6]) {
+        let mut store = Bookmarks::new();
+        let a = store.add_bookmark(BAR_ID, "a", "https://a.com/", Vec::new()).unwrap();
+        let folder = store.add_folder(BAR_ID, "folder").unwrap();
+        let b = store.add_bookmark(folder, "b", "https://b.com/", Vec::new()).unwrap();
+        let inner = store.add_folder(folder, "inner").unwrap();
+        let c = store.add_bookmark(inner, "c", "https://c.com/", Vec::new()).unwrap();
+        let d = store.add_bookmark(OTHER_ID, "d", "https://d.com/", Vec::new()).unwrap();
+        (store, [a, folder, b, inner, c, d])
+    }
+
+    #[test]
+    fn finds_nested_folders() {
+        let (mut store, [a, folder, _, inner, ..]) = sample();
+
+        store.folder_mut(inner).unwrap().title = "renamed".to_string();
+
+        assert_eq!(store.folder(inner).unwrap().title, "renamed");
+        assert_eq!(titles(store.folder(folder).unwrap()), ["b", "renamed"]);
+        assert_eq!(store.folder_mut(OTHER_ID).unwrap().id, OTHER_ID);
+        assert!(store.folder_mut(a).is_none());
+        assert!(store.folder_mut(999).is_none());
+    }
+
+    #[test]
+    fn removes_nodes_but_not_the_roots() {
+        let (mut store, [_, folder, _, _, c, d]) = sample();
+
+        assert_eq!(store.remove(d).unwrap().title(), "d");
+        assert!(store.other.children.is_empty());
+
+        assert_eq!(store.remove(folder).unwrap().title(), "folder");
+        assert!(store.node(c).is_none());
+        assert_eq!(titles(&store.bar), ["a"]);
+
+        assert!(store.remove(BAR_ID).is_none());
+        assert!(store.remove(OTHER_ID).is_none());
+        assert!(store.remove(folder).is_none());
+    }
+
+    #[test]
+    fn moves_nodes_between_and_within_folders() {
+        let (mut store, [a, folder, b, inner, c, _]) = sample();
+
+        assert!(store.move_node(c, OTHER_ID, 0));
+        assert_eq!(titles(&store.other), ["c", "d"]);
+        assert!(store.folder(inner).unwrap().children.is_empty());
+
+        assert!(store.move_node(a, folder, usize::MAX));
+        assert_eq!(titles(store.folder(folder).unwrap()), ["b", "inner", "a"]);
+
+        assert!(store.move_node(b, folder, 1));
+        assert_eq!(titles(store.folder(folder).unwrap()), ["inner", "b", "a"]);
+
+        assert!(!store.move_node(b, a, 0));
+        assert!(!store.move_node(999, folder, 0));
+        assert!(!store.move_node(b, 999, 0));
+    }
+
+    #[test]
+    fn folders_cant_move_into_themselves() {
+        let (mut store, [_, folder, _, inner, ..]) = sample();
+
+        assert!(!store.move_node(folder, folder, 0));
+        assert!(!store.move_node(folder, inner, 0));
+        assert_eq!(titles(&store.bar), ["a", "folder"]);
+        assert_eq!(titles(store.folder(folder).unwrap()), ["b", "inner"]);
+
+        assert!(store.move_node(inner, BAR_ID, 0));
+        assert_eq!(titles(&store.bar), ["inner", "a", "folder"]);
+    }
+
+    #[test]
+    fn inserted_trees_get_fresh_ids() {
+        let (mut store, ids) = sample();
+        let (other, _) = sample();
+
+        store.insert_tree(OTHER_ID, other.bar.children.clone());
+
+        assert_eq!(titles(&store.other), ["d", "a", "folder"]);
+        let mut all: Vec<u64> = store.all_bookmarks().iter().map(|b| b.id).collect();
+        all.extend(store.folders().iter().map(|(id, _, _)| *id));
+        let count = all.len();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), count);
+        assert_eq!(store.folders().iter().filter(|(_, title, _)| title == "inner").count(), 2);
+        assert_eq!(store.node(ids[4]).unwrap().title(), "c");
+        assert!(store.all_bookmarks().iter().all(|b| b.id < store.next_id));
+    }
+}
@@ -0,0 +1,462 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::bookmarks::{Bookmark, Bookmarks, Folder, Node};
+use crate::history::now;
+
+lazy_static! {
+    static ref TAG_PATTERN: Regex = Regex::new(r"(?s)<(/?)([a-zA-Z0-9]+)([^>]*)>").unwrap();
+    static ref ATTR_PATTERN: Regex = Regex::new(r#"([a-zA-Z_][a-zA-Z0-9_-]*)\s*=\s*"([^"]*)""#).unwrap();
+}
+
+/// Bookmarks read from another browser, split the same way as our store.
+#[derive(Debug, Default)]
+pub struct ImportedBookmarks {
+    pub bar: Vec<Node>,
+    pub other: Vec<Node>,
+}
+
+// Microseconds between 1601-01-01 (Chromium's epoch) and 1970-01-01
+const CHROMIUM_EPOCH_OFFSET: i64 = 11_644_473_600_000_000;
+
+fn bookmark(title: &str, url: &str, tags: Vec<String>, favicon: Option<String>, created: i64, modified: i64) -> Node {
+    Node::Bookmark(Bookmark {
+        id: 0,
+        title: title.to_string(),
+        url: url.to_string(),
+        tags,
+        favicon,
+        created,
+        modified,
+    })
+}
+
+fn folder(title: &str, children: Vec<Node>, created: i64, modified: i64) -> Folder {
+    Folder {
+        id: 0,
+        title: title.to_string(),
+        created,
+        modified,
+        children,
+    }
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn encode_entities(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Reads a bookmarks file, detecting whether it is a Netscape bookmark HTML
+/// file, a Chromium `Bookmarks` file or a Firefox `bookmarks.json` backup.
+pub fn import_file(path: &Path) -> Result<ImportedBookmarks, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    if data.trim_start().starts_with('{') {
+        let json: Value = serde_json::from_str(&data).map_err(|e| format!("Unable to parse JSON: {}", e))?;
+        if json.get("roots").is_some() {
+            Ok(import_chromium(&json))
+        } else if json.get("children").is_some() {
+            Ok(import_firefox(&json))
+        } else {
+            Err("Unrecognized bookmarks JSON".to_string())
+        }
+    } else if data.to_uppercase().contains("<DL") {
+        Ok(import_netscape(&data))
+    } else {
+        Err("Unrecognized bookmarks file".to_string())
+    }
+}
+
+// Name, attributes and text start of an open <H3> or <A>
+type OpenTag = (String, Vec<(String, String)>, usize);
+
+/// Parses the Netscape bookmark file format exported by every major browser.
+/// The folder marked `PERSONAL_TOOLBAR_FOLDER` becomes the bookmarks bar.
+pub fn import_netscape(html: &str) -> ImportedBookmarks {
+    let mut imported = ImportedBookmarks::default();
+
+    // Open folders; the bottom one is the file's top level list
+    let mut stack: Vec<(Folder, bool)> = Vec::new();
+    // Folder named by the last <H3>, opened by the following <DL>
+    let mut pending: Option<(Folder, bool)> = None;
+    // Text collection for the current <H3> or <A>
+    let mut current: Option<OpenTag> = None;
+
+    for tag in TAG_PATTERN.captures_iter(html) {
+        let whole = tag.get(0).unwrap();
+        let closing = &tag[1] == "/";
+        let name = tag[2].to_uppercase();
+
+        match (closing, name.as_str()) {
+            (false, "H3") | (false, "A") => {
+                let attrs = ATTR_PATTERN
+                    .captures_iter(&tag[3])
+                    .map(|a| (a[1].to_uppercase(), decode_entities(&a[2])))
+                    .collect();
+                current = Some((name, attrs, whole.end()));
+            }
+            (true, "H3") | (true, "A") => {
+                let Some((open, attrs, start)) = current.take() else {
+                    continue;
+                };
+                if open != name {
+                    continue;
+                }
+
+                let text = decode_entities(html[start..whole.start()].trim());
+                let attr = |key: &str| attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+                let time = |key: &str| attr(key).and_then(|v| v.parse::<i64>().ok()).unwrap_or_else(now);
+
+                if name == "H3" {
+                    let toolbar = attr("PERSONAL_TOOLBAR_FOLDER").is_some_and(|v| v == "true");
+                    pending = Some((folder(&text, Vec::new(), time("ADD_DATE"), time("LAST_MODIFIED")), toolbar));
+                } else if let Some(url) = attr("HREF") {
+                    let tags = attr("TAGS").map(|t| split_tags(&t)).unwrap_or_default();
+                    let favicon = attr("ICON").filter(|i| i.starts_with("data:image/png;base64,"));
+                    let node = bookmark(&text, &url, tags, favicon, time("ADD_DATE"), time("LAST_MODIFIED"));
+                    match stack.last_mut() {
+                        Some((parent, _)) => parent.children.push(node),
+                        None => imported.other.push(node),
+                    }
+                }
+            }
+            (false, "DL") => {
+                let opened = pending.take().unwrap_or_else(|| (folder("", Vec::new(), 0, 0), false));
+                stack.push(opened);
+            }
+            (true, "DL") => {
+                let Some((done, toolbar)) = stack.pop() else {
+                    continue;
+                };
+
+                // The toolbar folder is only special at the top level
+                if toolbar && stack.len() == 1 {
+                    imported.bar.extend(done.children);
+                    continue;
+                }
+
+                match stack.last_mut() {
+                    Some((parent, _)) => parent.children.push(Node::Folder(done)),
+                    None => imported.other.extend(done.children),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Tolerate files missing their closing tags
+    while let Some((done, _)) = stack.pop() {
+        match stack.last_mut() {
+            Some((parent, _)) => parent.children.push(Node::Folder(done)),
+            None => imported.other.extend(done.children),
+        }
+    }
+
+    imported
+}
+
+fn chromium_time(value: Option<&Value>) -> i64 {
+    value
+        .and_then(|v| v.as_str())
+        .and_then(|v| v.parse::<i64>().ok())
+        .map(|us| (us - CHROMIUM_EPOCH_OFFSET) / 1_000_000)
+        .unwrap_or_else(now)
+}
+
+fn chromium_nodes(parent: &Value) -> Vec<Node> {
+    let Some(children) = parent.get("children").and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+
+    children
+        .iter()
+        .filter_map(|child| {
+            let title = child.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            let created = chromium_time(child.get("date_added"));
+            let modified = child
+                .get("date_modified")
+                .map(|m| chromium_time(Some(m)))
+                .unwrap_or(created);
+
+            match child.get("type").and_then(|t| t.as_str()) {
+                Some("url") => {
+                    let url = child.get("url").and_then(|u| u.as_str())?;
+                    Some(bookmark(title, url, Vec::new(), None, created, modified))
+                }
+                Some("folder") => Some(Node::Folder(folder(title, chromium_nodes(child), created, modified))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses Chromium's `Bookmarks` profile file.
+pub fn import_chromium(json: &Value) -> ImportedBookmarks {
+    let roots = &json["roots"];
+    let mut imported = ImportedBookmarks {
+        bar: chromium_nodes(&roots["bookmark_bar"]),
+        other: chromium_nodes(&roots["other"]),
+    };
+    imported.other.extend(chromium_nodes(&roots["synced"]));
+    imported
+}
+
+fn firefox_time(value: Option<&Value>) -> i64 {
+    value
+        .and_then(|v| v.as_i64())
+        .map(|us| us / 1_000_000)
+        .unwrap_or_else(now)
+}
+
+fn firefox_nodes(parent: &Value) -> Vec<Node> {
+    let Some(children) = parent.get("children").and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+
+    children
+        .iter()
+        .filter_map(|child| {
+            let title = child.get("title").and_then(|t| t.as_str()).unwrap_or_default();
+            let created = firefox_time(child.get("dateAdded"));
+            let modified = child
+                .get("lastModified")
+                .map(|m| firefox_time(Some(m)))
+                .unwrap_or(created);
+
+            match child.get("typeCode").and_then(|t| t.as_i64()) {
+                Some(1) => {
+                    let url = child.get("uri").and_then(|u| u.as_str())?;
+                    let tags = child
+                        .get("tags")
+                        .and_then(|t| t.as_str())
+                        .map(split_tags)
+                        .unwrap_or_default();
+                    Some(bookmark(title, url, tags, None, created, modified))
+                }
+                Some(2) => Some(Node::Folder(folder(title, firefox_nodes(child), created, modified))),
+                // 3 is a separator
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses a Firefox `bookmarks-*.json` backup. The toolbar becomes the
+/// bookmarks bar; the menu, unfiled and mobile roots go to other bookmarks.
+/// Tags are read from each bookmark, so the tags root is skipped.
+pub fn import_firefox(json: &Value) -> ImportedBookmarks {
+    let mut imported = ImportedBookmarks::default();
+
+    let Some(roots) = json.get("children").and_then(|c| c.as_array()) else {
+        return imported;
+    };
+
+    for root in roots {
+        match root.get("root").and_then(|r| r.as_str()) {
+            Some("toolbarFolder") => imported.bar.extend(firefox_nodes(root)),
+            Some("tagsFolder") => {}
+            _ => imported.other.extend(firefox_nodes(root)),
+        }
+    }
+
+    imported
+}
+
+fn export_nodes(nodes: &[Node], depth: usize, out: &mut String) {
+    let indent = "    ".repeat(depth);
+
+    for node in nodes {
+        match node {
+            Node::Bookmark(b) => {
+                out.push_str(&format!(
+                    "{}<DT><A HREF=\"{}\" ADD_DATE=\"{}\" LAST_MODIFIED=\"{}\"",
+                    indent,
+                    encode_entities(&b.url),
+                    b.created,
+                    b.modified
+                ));
+                if !b.tags.is_empty() {
+                    out.push_str(&format!(" TAGS=\"{}\"", encode_entities(&b.tags.join(","))));
+                }
+                if let Some(icon) = &b.favicon {
+                    out.push_str(&format!(" ICON=\"{}\"", encode_entities(icon)));
+                }
+                out.push_str(&format!(">{}</A>\n", encode_entities(&b.title)));
+            }
+            Node::Folder(f) => export_folder(f, depth, false, out),
+        }
+    }
+}
+
+fn export_folder(folder: &Folder, depth: usize, toolbar: bool, out: &mut String) {
+    let indent = "    ".repeat(depth);
+
+    out.push_str(&format!(
+        "{}<DT><H3 ADD_DATE=\"{}\" LAST_MODIFIED=\"{}\"{}>{}</H3>\n",
+        indent,
+        folder.created,
+        folder.modified,
+        if toolbar { " PERSONAL_TOOLBAR_FOLDER=\"true\"" } else { "" },
+        encode_entities(&folder.title)
+    ));
+    out.push_str(&format!("{}<DL><p>\n", indent));
+    export_nodes(&folder.children, depth + 1, out);
+    out.push_str(&format!("{}</DL><p>\n", indent));
+}
+
+/// Writes the bookmarks in the Netscape bookmark file format, with the bar
+/// as the toolbar folder and other bookmarks at the top level.
+pub fn export_netscape(bookmarks: &Bookmarks) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+         <!-- This is an automatically generated file.\n     \
+         It will be read and overwritten.\n     \
+         DO NOT EDIT! -->\n\
+         <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+         <TITLE>Bookmarks</TITLE>\n\
+         <H1>Bookmarks</H1>\n\
+         <DL><p>\n",
+    );
+
+    export_folder(&bookmarks.bar, 1, true, &mut out);
+    export_nodes(&bookmarks.other.children, 1, &mut out);
+
+    out.push_str("</DL><p>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmarks::{BAR_ID, OTHER_ID};
+
+    fn fixture(name: &str) -> ImportedBookmarks {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        import_file(&path).unwrap()
+    }
+
+    // One line per node: folders as "path/", bookmarks as
+    // "path/title <url> [tags] created"
+    fn outline(nodes: &[Node], path: &str, out: &mut Vec<String>) {
+        for node in nodes {
+            match node {
+                Node::Bookmark(b) => out.push(format!(
+                    "{}{} <{}> [{}] {}",
+                    path,
+                    b.title,
+                    b.url,
+                    b.tags.join(","),
+                    b.created
+                )),
+                Node::Folder(f) => {
+                    let path = format!("{}{}/", path, f.title);
+                    out.push(path.clone());
+                    outline(&f.children, &path, out);
+                }
+            }
+        }
+    }
+
+    fn outlines(imported: &ImportedBookmarks) -> (Vec<String>, Vec<String>) {
+        let (mut bar, mut other) = (Vec::new(), Vec::new());
+        outline(&imported.bar, "", &mut bar);
+        outline(&imported.other, "", &mut other);
+        (bar, other)
+    }
+
+    fn round_trip(imported: ImportedBookmarks) -> ImportedBookmarks {
+        let mut store = Bookmarks::new();
+        store.insert_tree(BAR_ID, imported.bar);
+        store.insert_tree(OTHER_ID, imported.other);
+        import_netscape(&export_netscape(&store))
+    }
+
+    fn assert_round_trip(name: &str, bar: &[&str], other: &[&str]) {
+        let imported = fixture(name);
+        let expected = (
+            bar.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            other.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+        );
+        assert_eq!(outlines(&imported), expected);
+        assert_eq!(outlines(&round_trip(imported)), expected);
+    }
+
+    #[test]
+    fn netscape_round_trip() {
+        assert_round_trip(
+            "bookmarks.html",
+            &[
+                "Rust <https://www.rust-lang.org/> [rust,language] 1700000001",
+                "News/",
+                "News/LWN.net <https://lwn.net/> [] 1700000005",
+            ],
+            &[
+                "Reference/",
+                "Reference/Web & APIs/",
+                "Reference/Web & APIs/MDN <Web Docs> <https://developer.mozilla.org/?q=a&b> [docs,web] 1700000014",
+                "Reference/GTK 4 <https://docs.gtk.org/gtk4/> [gtk] 1700000015",
+                "Example <https://example.com/> [] 1700000020",
+            ],
+        );
+    }
+
+    #[test]
+    fn chromium_round_trip() {
+        assert_round_trip(
+            "Bookmarks",
+            &[
+                "Rust <https://www.rust-lang.org/> [] 1700526400",
+                "News/",
+                "News/LWN.net <https://lwn.net/> [] 1700526402",
+            ],
+            &[
+                "Reference/",
+                "Reference/GTK 4 <https://docs.gtk.org/gtk4/> [] 1700526404",
+                "Example <https://example.com/> [] 1700526405",
+            ],
+        );
+    }
+
+    #[test]
+    fn firefox_round_trip() {
+        assert_round_trip(
+            "bookmarks.json",
+            &[
+                "Rust <https://www.rust-lang.org/> [rust,language] 1700000001",
+                "News/",
+                "News/LWN.net <https://lwn.net/> [] 1700000005",
+            ],
+            &[
+                "Reference/",
+                "Reference/GTK 4 <https://docs.gtk.org/gtk4/> [gtk] 1700000015",
+                "Example <https://example.com/> [] 1700000020",
+            ],
+        );
+    }
+
+    #[test]
+    fn unknown_files_are_rejected() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert!(import_file(&path).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use gtk4::{
+    gio, glib, prelude::*, ApplicationWindow, Box, Button, CheckButton, DropDown, Label, Orientation, Window,
+};
+use gtk4::glib::thread_guard::ThreadGuard;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::rc::Rc;
+use webkit6::WebsiteDataTypes;
+
+use crate::downloads::clear_downloads_since;
+use crate::files::save_json;
+use crate::history::{history, now};
+use crate::network::network_session;
+use crate::paths::config_path;
+
+const CLEAR_DATA_FILE: &str = "clear_data.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeRange {
+    LastHour,
+    LastDay,
+    LastWeek,
+    AllTime,
+}
+
+const TIME_RANGES: [(TimeRange, &str); 4] = [
+    (TimeRange::LastHour, "Last hour"),
+    (TimeRange::LastDay, "Last 24 hours"),
+    (TimeRange::LastWeek, "Last 7 days"),
+    (TimeRange::AllTime, "All time"),
+];
+
+impl TimeRange {
+    fn seconds(self) -> Option<i64> {
+        match self {
+            TimeRange::LastHour => Some(60 * 60),
+            TimeRange::LastDay => Some(24 * 60 * 60),
+            TimeRange::LastWeek => Some(7 * 24 * 60 * 60),
+            TimeRange::AllTime => None,
+        }
+    }
+}
+
+/// What the clear browsing data dialog clears, remembered between uses.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClearOptions {
+    pub range: TimeRange,
+    pub history: bool,
+    pub cookies: bool,
+    pub cache: bool,
+    pub site_storage: bool,
+    pub downloads: bool,
+    /// Clear the selected data from all time whenever the browser quits.
+    pub on_exit: bool,
+}
+
+impl Default for ClearOptions {
+    fn default() -> Self {
+        ClearOptions {
+            range: TimeRange::LastHour,
+            history: true,
+            cookies: true,
+            cache: true,
+            site_storage: true,
+            downloads: false,
+            on_exit: false,
+        }
+    }
+}
+
+impl ClearOptions {
+    fn website_data_types(&self) -> WebsiteDataTypes {
+        let mut types = WebsiteDataTypes::empty();
+        if self.cookies {
+            types |= WebsiteDataTypes::COOKIES;
+        }
+        if self.cache {
+            types |= WebsiteDataTypes::MEMORY_CACHE | WebsiteDataTypes::DISK_CACHE;
+        }
+        if self.site_storage {
+            types |= WebsiteDataTypes::LOCAL_STORAGE
+                | WebsiteDataTypes::SESSION_STORAGE
+                | WebsiteDataTypes::INDEXEDDB_DATABASES
+                | WebsiteDataTypes::DOM_CACHE
+                | WebsiteDataTypes::SERVICE_WORKER_REGISTRATIONS;
+        }
+        types
+    }
+}
+
+// Runs at shutdown, so a broken file falls back to the defaults instead of
+// stopping the browser from quitting
+fn load_clear_options() -> ClearOptions {
+    let path = config_path(CLEAR_DATA_FILE);
+    if !path.exists() {
+        return ClearOptions::default();
+    }
+
+    let loaded = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()));
+    loaded.unwrap_or_else(|e| {
+        eprintln!("Unable to load {}, using the defaults: {}", path.display(), e);
+        ClearOptions::default()
+    })
+}
+
+fn save_clear_options(options: &ClearOptions) {
+    save_json(&config_path(CLEAR_DATA_FILE), options);
+}
+
+/// Clears the data selected in `options` from its time range, calling `done`
+/// once the website data is gone as well.
+pub fn clear_browsing_data<F: FnOnce() + 'static>(options: &ClearOptions, done: F) {
+    let seconds = options.range.seconds();
+    let since = seconds.map_or(i64::MIN, |s| now() - s);
+
+    if options.history {
+        history().borrow_mut().clear_range(since, i64::MAX);
+    }
+    if options.downloads {
+        clear_downloads_since(since);
+    }
+
+    let types = options.website_data_types();
+    let Some(manager) = network_session().website_data_manager().filter(|_| !types.is_empty()) else {
+        done();
+        return;
+    };
+
+    // A time span of 0 clears everything
+    let timespan = glib::TimeSpan::from_seconds(seconds.unwrap_or(0));
+    // The callback runs on the main thread, but the binding asks for Send
+    let done = ThreadGuard::new(done);
+    manager.clear(types, timespan, None::<&gio::Cancellable>, move |result| {
+        if let Err(e) = result {
+            println!("Unable to clear website data: {}", e);
+        }
+        (done.into_inner())();
+    });
+}
+
+/// Clears the data selected for clearing on exit. Runs the main loop until
+/// WebKit is done, as the application's loop has already stopped.
+pub fn clear_on_exit() {
+    let options = load_clear_options();
+    if !options.on_exit {
+        return;
+    }
+
+    let options = ClearOptions {
+        range: TimeRange::AllTime,
+        ..options
+    };
+
+    let main_loop = glib::MainLoop::new(None, false);
+    let main_loop_clone = main_loop.clone();
+    clear_browsing_data(&options, move || main_loop_clone.quit());
+    main_loop.run();
+}
+
+pub fn create_clear_data_dialog(parent: &ApplicationWindow) {
+    let options = load_clear_options();
+
+    let dialog = Window::new();
+    dialog.set_title(Some("Clear browsing data"));
+    dialog.set_transient_for(Some(parent));
+    dialog.set_modal(true);
+    dialog.set_resizable(false);
+
+    let vbox = Box::new(Orientation::Vertical, 10);
+    vbox.set_margin_top(10);
+    vbox.set_margin_bottom(10);
+    vbox.set_margin_start(10);
+    vbox.set_margin_end(10);
+
+    let range_row = Box::new(Orientation::Horizontal, 10);
+    let range_label = Label::new(Some("Time range"));
+    range_label.set_hexpand(true);
+    range_label.set_xalign(0.0);
+    range_row.append(&range_label);
+
+    let labels: Vec<&str> = TIME_RANGES.iter().map(|(_, label)| *label).collect();
+    let range = DropDown::from_strings(&labels);
+    if let Some(selected) = TIME_RANGES.iter().position(|(r, _)| *r == options.range) {
+        range.set_selected(selected as u32);
+    }
+    range_row.append(&range);
+    vbox.append(&range_row);
+
+    let check = |label: &str, active: bool| {
+        let button = CheckButton::with_label(label);
+        button.set_active(active);
+        vbox.append(&button);
+        button
+    };
+    let history = check("Browsing history", options.history);
+    let cookies = check("Cookies", options.cookies);
+    let cache = check("Cached images and files", options.cache);
+    let site_storage = check("Site storage and databases", options.site_storage);
+    let downloads = check("Downloads list", options.downloads);
+    // No form data entry, as WebKitGTK doesn't store any to clear
+    let on_exit = check("Clear this data whenever Rubra quits", options.on_exit);
+
+    let buttons = Box::new(Orientation::Horizontal, 10);
+    buttons.set_halign(gtk4::Align::End);
+    let cancel = Button::with_label("Cancel");
+    let clear = Button::with_label("Clear Data");
+    clear.add_css_class("destructive-action");
+    buttons.append(&cancel);
+    buttons.append(&clear);
+    vbox.append(&buttons);
+
+    let dialog_clone = dialog.clone();
+    cancel.connect_clicked(move |_| dialog_clone.close());
+
+    // Weak, as the widgets' own handlers hold this closure
+    let selected = {
+        let range = range.downgrade();
+        let [history, cookies, cache, site_storage, downloads, on_exit] =
+            [&history, &cookies, &cache, &site_storage, &downloads, &on_exit].map(|b| b.downgrade());
+        Rc::new(move || {
+            Some(ClearOptions {
+                range: TIME_RANGES
+                    .get(range.upgrade()?.selected() as usize)
+                    .map_or(TimeRange::LastHour, |(r, _)| *r),
+                history: history.upgrade()?.is_active(),
+                cookies: cookies.upgrade()?.is_active(),
+                cache: cache.upgrade()?.is_active(),
+                site_storage: site_storage.upgrade()?.is_active(),
+                downloads: downloads.upgrade()?.is_active(),
+                on_exit: on_exit.upgrade()?.is_active(),
+            })
+        })
+    };
+
+    // Saved on every change, so turning on clearing on exit doesn't need
+    // anything to be cleared now
+    for button in [&history, &cookies, &cache, &site_storage, &downloads, &on_exit] {
+        let selected = Rc::clone(&selected);
+        button.connect_toggled(move |_| {
+            if let Some(options) = selected() {
+                save_clear_options(&options);
+            }
+        });
+    }
+    let selected_clone = Rc::clone(&selected);
+    range.connect_selected_notify(move |_| {
+        if let Some(options) = selected_clone() {
+            save_clear_options(&options);
+        }
+    });
+
+    let dialog_clone = dialog.clone();
+    clear.connect_clicked(move |clear| {
+        let Some(options) = selected() else {
+            return;
+        };
+
+        clear.set_sensitive(false);
+        let dialog = dialog_clone.clone();
+        clear_browsing_data(&options, move || dialog.close());
+    });
+
+    dialog.set_child(Some(&vbox));
+    dialog.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_ranges_reach_back() {
+        assert_eq!(TimeRange::LastHour.seconds(), Some(3600));
+        assert_eq!(TimeRange::LastDay.seconds(), Some(86400));
+        assert_eq!(TimeRange::LastWeek.seconds(), Some(604800));
+        assert_eq!(TimeRange::AllTime.seconds(), None);
+    }
+
+    #[test]
+    fn selects_website_data_types() {
+        let none = ClearOptions {
+            cookies: false,
+            cache: false,
+            site_storage: false,
+            ..ClearOptions::default()
+        };
+        assert!(none.website_data_types().is_empty());
+
+        let cookies = ClearOptions { cookies: true, ..none.clone() };
+        assert_eq!(cookies.website_data_types(), WebsiteDataTypes::COOKIES);
+
+        let cache = ClearOptions { cache: true, ..none.clone() }.website_data_types();
+        assert!(cache.contains(WebsiteDataTypes::MEMORY_CACHE | WebsiteDataTypes::DISK_CACHE));
+        assert!(!cache.intersects(WebsiteDataTypes::COOKIES | WebsiteDataTypes::LOCAL_STORAGE));
+
+        let storage = ClearOptions { site_storage: true, ..none }.website_data_types();
+        assert!(storage.contains(WebsiteDataTypes::LOCAL_STORAGE | WebsiteDataTypes::INDEXEDDB_DATABASES));
+        assert!(!storage.intersects(WebsiteDataTypes::COOKIES | WebsiteDataTypes::DISK_CACHE));
+    }
+
+    #[test]
+    fn missing_options_use_the_defaults() {
+        let options: ClearOptions = serde_json::from_str(r#"{ "range": "last_week", "on_exit": true }"#).unwrap();
+        assert_eq!(options.range, TimeRange::LastWeek);
+        assert!(options.on_exit);
+        assert!(options.history && options.cookies && options.cache && options.site_storage);
+        assert!(!options.downloads);
+
+        let options: ClearOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options.range, TimeRange::LastHour);
+        assert!(!options.on_exit);
+    }
+}
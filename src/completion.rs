@@ -0,0 +1,501 @@
+use gtk4::glib::Propagation;
+use gtk4::{
+    gdk, prelude::*, Box, Entry, EventControllerFocus, EventControllerKey, Label, ListBox, ListBoxRow, Orientation,
+    Popover, PositionType, PropagationPhase, StateFlags,
+};
+use gtk4::pango::EllipsizeMode;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use url::Url;
+use webkit6::prelude::*;
+use webkit6::WebView;
+
+use crate::bookmarks::{bookmarks, Bookmark};
+use crate::history::{history, now, strip_url_prefix, HistoryEntry, Transition};
+use crate::tab::{tabs, Tab};
+use crate::window::is_private;
+
+const MAX_SUGGESTIONS: usize = 8;
+
+// Scores per unit of match weight, which goes from 1 to 2. The worst match of
+// each kind still beats the best of the next, so open tabs come before
+// bookmarks and bookmarks before history however often it was visited.
+const OPEN_TAB_SCORE: f64 = 700.0;
+const BOOKMARK_SCORE: f64 = 300.0;
+const HISTORY_SCORE: f64 = 150.0;
+// The frecency that earns half of `HISTORY_SCORE`
+const HALF_SCORE_FRECENCY: f64 = 500.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestionKind {
+    History,
+    Bookmark,
+    OpenTab,
+}
+
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    pub title: String,
+    pub url: String,
+    score: f64,
+    /// The tab to switch to, for open tab suggestions.
+    pub tab: Option<Rc<Tab>>,
+}
+
+pub struct Completion {
+    entry: Entry,
+    popover: Popover,
+    list: ListBox,
+    suggestions: RefCell<Vec<Suggestion>>,
+    // Set while the entry text is changed by us rather than by typing
+    suppress: Cell<bool>,
+    typed_len: Cell<usize>,
+}
+
+fn host_of(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+// How well `url`/`title` match the typed text, 0 for no match
+fn match_weight(input: &str, url: &str, title: &str) -> f64 {
+    let url = url.to_lowercase();
+    let title = title.to_lowercase();
+
+    if strip_url_prefix(&url).starts_with(input) || url.starts_with(input) {
+        2.0
+    } else if title.starts_with(input) {
+        1.5
+    } else if url.contains(input) || title.contains(input) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Frecency: visit count weighted by how recently the page was visited,
+/// with typed URLs counting more than followed links.
+fn frecency(entry: &HistoryEntry) -> f64 {
+    let age_days = (now() - entry.last_visit) / (24 * 60 * 60);
+    let recency = match age_days {
+        0..=1 => 100.0,
+        2..=7 => 70.0,
+        8..=30 => 50.0,
+        31..=90 => 30.0,
+        _ => 10.0,
+    };
+    let typed = if entry.transition == Transition::Typed { 1.5 } else { 1.0 };
+
+    entry.visit_count as f64 * recency * typed
+}
+
+// Frecency squashed into `0..HISTORY_SCORE`
+fn history_score(entry: &HistoryEntry) -> f64 {
+    let frecency = frecency(entry);
+    HISTORY_SCORE * frecency / (frecency + HALF_SCORE_FRECENCY)
+}
+
+/// Ranked suggestions for `input` from open tabs, bookmarks and history.
+/// `current` is left out of the open tabs, as are tabs on the other side of
+/// the private browsing divide.
+pub fn suggest(current: &WebView, input: &str) -> Vec<Suggestion> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return Vec::new();
+    }
+
+    let private = is_private(current);
+    let open_tabs = tabs()
+        .into_iter()
+        .filter(|tab| &tab.webview != current && is_private(&tab.webview) == private)
+        .map(|tab| Suggestion {
+            kind: SuggestionKind::OpenTab,
+            title: tab.webview.title().map(|t| t.to_string()).unwrap_or_default(),
+            url: tab.webview.uri().map(|u| u.to_string()).unwrap_or_default(),
+            score: 0.0,
+            tab: Some(tab),
+        })
+        .collect();
+
+    let bookmarks = bookmarks();
+    let bookmarks = bookmarks.borrow();
+    let entries = history().borrow().entries();
+    rank(&input, open_tabs, &bookmarks.all_bookmarks(), entries)
+}
+
+// Scores the candidates against `input`, which is trimmed and lowercase. A URL
+// is only suggested once, as an open tab before a bookmark before history.
+fn rank(
+    input: &str,
+    open_tabs: Vec<Suggestion>,
+    bookmarks: &[&Bookmark],
+    entries: Vec<HistoryEntry>,
+) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+
+    for mut tab in open_tabs {
+        let weight = match_weight(input, &tab.url, &tab.title);
+        if weight > 0.0 {
+            tab.score = OPEN_TAB_SCORE * weight;
+            suggestions.push(tab);
+        }
+    }
+
+    for bookmark in bookmarks {
+        if suggestions.iter().any(|s| s.url == bookmark.url) {
+            continue;
+        }
+
+        let tags = bookmark.tags.join(" ");
+        let weight = match_weight(input, &bookmark.url, &bookmark.title).max(match_weight(input, "", &tags));
+
+        if weight > 0.0 {
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::Bookmark,
+                title: bookmark.title.clone(),
+                url: bookmark.url.clone(),
+                score: BOOKMARK_SCORE * weight,
+                tab: None,
+            });
+        }
+    }
+
+    for entry in entries {
+        if suggestions.iter().any(|s| s.url == entry.url) {
+            continue;
+        }
+
+        let weight = match_weight(input, &entry.url, &entry.title);
+        if weight > 0.0 {
+            suggestions.push(Suggestion {
+                kind: SuggestionKind::History,
+                score: history_score(&entry) * weight,
+                title: entry.title,
+                url: entry.url,
+                tab: None,
+            });
+        }
+    }
+
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+impl Completion {
+    /// Attaches a suggestion popover to the address bar of the tab showing
+    /// `webview`.
+    pub fn attach(entry: &Entry, webview: &WebView) -> Rc<Completion> {
+        let list = ListBox::new();
+        list.set_focusable(false);
+
+        let popover = Popover::new();
+        popover.set_child(Some(&list));
+        popover.set_parent(entry);
+        popover.set_autohide(false);
+        popover.set_has_arrow(false);
+        popover.set_position(PositionType::Bottom);
+        popover.set_halign(gtk4::Align::Start);
+
+        let completion = Rc::new(Completion {
+            entry: entry.clone(),
+            popover,
+            list,
+            suggestions: RefCell::new(Vec::new()),
+            suppress: Cell::new(false),
+            typed_len: Cell::new(0),
+        });
+
+        let completion_clone = Rc::clone(&completion);
+        let webview_clone = webview.clone();
+        entry.connect_changed(move |entry| {
+            // Ignore our own edits and URL updates from the web view
+            if completion_clone.suppress.get() || !entry.state_flags().contains(StateFlags::FOCUS_WITHIN) {
+                return;
+            }
+
+            let text = entry.text().to_string();
+            let suggestions = suggest(&webview_clone, &text);
+            completion_clone.show(suggestions);
+
+            let len = text.chars().count();
+            let grew = len > completion_clone.typed_len.get();
+            completion_clone.typed_len.set(len);
+
+            if grew {
+                let completion = Rc::clone(&completion_clone);
+                gtk4::glib::idle_add_local_once(move || completion.complete_inline(&text));
+            }
+        });
+
+        let key_controller = EventControllerKey::new();
+        key_controller.set_propagation_phase(PropagationPhase::Capture);
+        let completion_clone = Rc::clone(&completion);
+        key_controller.connect_key_pressed(move |_, key, _, _| {
+            if !completion_clone.popover.is_visible() {
+                return Propagation::Proceed;
+            }
+
+            match key {
+                gdk::Key::Down => {
+                    completion_clone.move_selection(1);
+                    Propagation::Stop
+                }
+                gdk::Key::Up => {
+                    completion_clone.move_selection(-1);
+                    Propagation::Stop
+                }
+                gdk::Key::Escape => {
+                    completion_clone.hide();
+                    Propagation::Stop
+                }
+                _ => Propagation::Proceed,
+            }
+        });
+        entry.add_controller(key_controller);
+
+        let focus_controller = EventControllerFocus::new();
+        let completion_clone = Rc::clone(&completion);
+        focus_controller.connect_leave(move |_| {
+            completion_clone.hide();
+        });
+        entry.add_controller(focus_controller);
+
+        let entry_clone = entry.clone();
+        completion.list.connect_row_activated(move |list, row| {
+            list.select_row(Some(row));
+            entry_clone.emit_activate();
+        });
+
+        let popover = completion.popover.clone();
+        entry.connect_destroy(move |_| {
+            popover.unparent();
+        });
+
+        completion
+    }
+
+    fn show(&self, suggestions: Vec<Suggestion>) {
+        self.list.remove_all();
+
+        if suggestions.is_empty() {
+            self.hide();
+            return;
+        }
+
+        for suggestion in &suggestions {
+            let row = ListBoxRow::new();
+            row.set_focusable(false);
+
+            let hbox = Box::new(Orientation::Horizontal, 10);
+
+            match suggestion.kind {
+                SuggestionKind::OpenTab => hbox.append(&Label::new(Some("Switch to tab:"))),
+                SuggestionKind::Bookmark => hbox.append(&Label::new(Some("★"))),
+                SuggestionKind::History => {}
+            }
+
+            let title = Label::new(Some(if suggestion.title.is_empty() { &suggestion.url } else { &suggestion.title }));
+            title.set_ellipsize(EllipsizeMode::End);
+            title.set_max_width_chars(50);
+            hbox.append(&title);
+
+            let url = Label::new(Some(&suggestion.url));
+            url.set_ellipsize(EllipsizeMode::End);
+            url.set_max_width_chars(60);
+            url.add_css_class("dim-label");
+            hbox.append(&url);
+
+            row.set_child(Some(&hbox));
+            self.list.append(&row);
+        }
+
+        *self.suggestions.borrow_mut() = suggestions;
+
+        self.popover.set_size_request(self.entry.width(), -1);
+        self.popover.popup();
+    }
+
+    pub fn hide(&self) {
+        self.list.unselect_all();
+        self.popover.popdown();
+    }
+
+    fn move_selection(&self, step: i32) {
+        let count = self.suggestions.borrow().len() as i32;
+        if count == 0 {
+            return;
+        }
+
+        let index = match self.list.selected_row() {
+            Some(row) => (row.index() + step).rem_euclid(count + 1),
+            None if step > 0 => 0,
+            None => count - 1,
+        };
+
+        // Moving past either end goes back to what was typed
+        match self.list.row_at_index(index) {
+            Some(row) if index < count => self.list.select_row(Some(&row)),
+            _ => self.list.unselect_all(),
+        }
+    }
+
+    // Completes the typed text to the domain of the best matching suggestion,
+    // leaving the completed part selected so typing replaces it
+    fn complete_inline(&self, typed: &str) {
+        if self.entry.text() != typed
+            || self.entry.position() as usize != typed.chars().count()
+            || typed.contains('/')
+            || typed.contains(' ')
+        {
+            return;
+        }
+
+        let lower = typed.to_lowercase();
+        let host = self
+            .suggestions
+            .borrow()
+            .iter()
+            .filter_map(|s| host_of(&s.url))
+            .find(|host| host.starts_with(&lower) && host.len() > lower.len());
+
+        if let Some(host) = host {
+            let completed = format!("{}{}", typed, &host[lower.len()..]);
+
+            self.suppress.set(true);
+            self.entry.set_text(&completed);
+            self.entry.select_region(typed.chars().count() as i32, -1);
+            self.suppress.set(false);
+        }
+    }
+
+    /// The suggestion picked with the keyboard or mouse, if any.
+    pub fn picked(&self) -> Option<Suggestion> {
+        let row = self.list.selected_row()?;
+        self.suggestions.borrow().get(row.index() as usize).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn entry(url: &str, visit_count: u32, age_days: i64, transition: Transition) -> HistoryEntry {
+        HistoryEntry {
+            url: url.to_string(),
+            title: String::new(),
+            last_visit: now() - age_days * DAY,
+            visit_count,
+            transition,
+        }
+    }
+
+    fn bookmark(url: &str, title: &str, tags: &[&str]) -> Bookmark {
+        Bookmark {
+            id: 0,
+            title: title.to_string(),
+            url: url.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            favicon: None,
+            created: 0,
+            modified: 0,
+        }
+    }
+
+    fn open_tab(url: &str, title: &str) -> Suggestion {
+        Suggestion {
+            kind: SuggestionKind::OpenTab,
+            title: title.to_string(),
+            url: url.to_string(),
+            score: 0.0,
+            tab: None,
+        }
+    }
+
+    #[test]
+    fn match_weights() {
+        assert_eq!(match_weight("exa", "https://www.example.com/", "Example"), 2.0);
+        assert_eq!(match_weight("https://ex", "https://example.com/", ""), 2.0);
+        assert_eq!(match_weight("news", "https://example.com/", "News of the day"), 1.5);
+        assert_eq!(match_weight("day", "https://example.com/", "News of the day"), 1.0);
+        assert_eq!(match_weight("ample", "https://example.com/", ""), 1.0);
+        assert_eq!(match_weight("rust", "https://example.com/", "Example"), 0.0);
+    }
+
+    #[test]
+    fn frecency_prefers_recent_frequent_and_typed_visits() {
+        let recent = frecency(&entry("a", 1, 0, Transition::Link));
+        let old = frecency(&entry("a", 1, 100, Transition::Link));
+        let frequent = frecency(&entry("a", 3, 0, Transition::Link));
+        let typed = frecency(&entry("a", 1, 0, Transition::Typed));
+
+        assert_eq!(recent, 100.0);
+        assert_eq!(old, 10.0);
+        assert_eq!(frequent, 300.0);
+        assert_eq!(typed, 150.0);
+    }
+
+    #[test]
+    fn history_scores_stay_below_bookmarks() {
+        let rare = history_score(&entry("a", 1, 100, Transition::Link));
+        let frequent = history_score(&entry("a", 10_000, 0, Transition::Typed));
+
+        assert!(rare > 0.0);
+        assert!(rare < frequent);
+        assert!(frequent < HISTORY_SCORE);
+        assert_eq!(history_score(&entry("a", 5, 0, Transition::Link)), HISTORY_SCORE / 2.0);
+    }
+
+    #[test]
+    fn ranks_tabs_then_bookmarks_then_history() {
+        let tabs = vec![open_tab("https://a.org/", "An example tab"), open_tab("https://rust-lang.org/", "Rust")];
+        let marked = bookmark("https://example.com/bookmark", "Bookmark", &[]);
+        let tagged = bookmark("https://docs.rs/", "Docs", &["more", "example"]);
+        let history = vec![
+            entry("https://example.com/history", 10_000, 0, Transition::Typed),
+            entry("https://example.com/bookmark", 50, 0, Transition::Typed),
+        ];
+
+        let ranked = rank("example", tabs, &[&marked, &tagged], history);
+        let urls: Vec<_> = ranked.iter().map(|s| s.url.as_str()).collect();
+
+        // Weakest matching tab and bookmark first, then the strongest history
+        assert_eq!(
+            urls,
+            ["https://a.org/", "https://example.com/bookmark", "https://docs.rs/", "https://example.com/history"]
+        );
+        assert_eq!(ranked[1].kind, SuggestionKind::Bookmark);
+        assert_eq!(ranked[3].kind, SuggestionKind::History);
+    }
+
+    #[test]
+    fn ranks_history_by_frecency_and_match() {
+        let history = vec![
+            entry("https://old.example.com/", 5, 100, Transition::Link),
+            entry("https://example.com/", 2, 0, Transition::Link),
+            entry("https://other.org/example", 2, 0, Transition::Link),
+            entry("https://unrelated.org/", 9, 0, Transition::Typed),
+        ];
+
+        let ranked = rank("example", Vec::new(), &[], history);
+        let urls: Vec<_> = ranked.iter().map(|s| s.url.as_str()).collect();
+
+        assert_eq!(urls, ["https://example.com/", "https://other.org/example", "https://old.example.com/"]);
+    }
+
+    #[test]
+    fn keeps_the_best_suggestions() {
+        let history = (0..20)
+            .map(|i| entry(&format!("https://example.com/{}", i), i + 1, 0, Transition::Link))
+            .collect();
+
+        let ranked = rank("example", Vec::new(), &[], history);
+
+        assert_eq!(ranked.len(), MAX_SUGGESTIONS);
+        assert_eq!(ranked[0].url, "https://example.com/19");
+    }
+}
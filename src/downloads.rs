@@ -0,0 +1,762 @@
+use gtk4::glib::{self, ControlFlow};
+use gtk4::pango::EllipsizeMode;
+use gtk4::{
+    gio, prelude::*, Box, Button, FileDialog, FileLauncher, Label, MenuButton, Orientation, Popover, ProgressBar,
+    Window,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use webkit6::{Download, NetworkSession};
+
+use crate::files::{load_json, save_json};
+use crate::history::now;
+use crate::network::network_session;
+use crate::setting::settings;
+use crate::tab::create_tab;
+use crate::window::window_for_widget;
+use crate::paths::data_path;
+use crate::listeners::Listeners;
+
+const DOWNLOADS_FILE: &str = "downloads.json";
+// Downloads shown in the toolbar popover, the rest are on rubra://downloads
+const POPOVER_DOWNLOADS: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    InProgress,
+    /// WebKit can't pause a transfer, so a paused download is stopped and
+    /// starts over from the beginning when restarted.
+    Paused,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadEntry {
+    pub id: u64,
+    pub url: String,
+    /// Path the file is saved to, empty until one has been chosen.
+    pub destination: String,
+    pub state: DownloadState,
+    pub received: u64,
+    /// Size in bytes, 0 when the server didn't say.
+    pub total: u64,
+    pub started: i64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl DownloadEntry {
+    pub fn file_name(&self) -> String {
+        Path::new(&self.destination)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .or_else(|| self.url.rsplit('/').find(|s| !s.is_empty()).map(|s| s.to_string()))
+            .unwrap_or_else(|| self.url.clone())
+    }
+
+    /// Fraction downloaded, `None` if the size is unknown.
+    pub fn progress(&self) -> Option<f64> {
+        (self.total > 0).then(|| (self.received as f64 / self.total as f64).min(1.0))
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == DownloadState::InProgress
+    }
+
+    // Moves a running download to `state`, or a paused one to cancelled
+    fn stop(&mut self, state: DownloadState) {
+        match (self.state, state) {
+            (DownloadState::InProgress, _) | (DownloadState::Paused, DownloadState::Cancelled) => self.state = state,
+            _ => {}
+        }
+    }
+
+    pub fn status(&self) -> String {
+        match self.state {
+            DownloadState::InProgress if self.total > 0 => {
+                format!("{} of {}", format_size(self.received), format_size(self.total))
+            }
+            DownloadState::InProgress => format_size(self.received),
+            DownloadState::Paused => format!("Paused at {}, restarting starts over", format_size(self.received)),
+            DownloadState::Completed => format!("Completed, {}", format_size(self.received)),
+            DownloadState::Cancelled => "Cancelled".to_string(),
+            DownloadState::Failed => match &self.error {
+                Some(error) => format!("Failed: {}", error),
+                None => "Failed".to_string(),
+            },
+        }
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+pub struct Downloads {
+    entries: Vec<DownloadEntry>,
+    next_id: u64,
+    // Transfers currently running, by entry id
+    active: HashMap<u64, Download>,
+    // Entry restarted by `restart`, claimed by the next download to start
+    restarting: Option<u64>,
+}
+
+thread_local! {
+    static DOWNLOADS: Rc<RefCell<Downloads>> = Rc::new(RefCell::new(Downloads::load()));
+    static LISTENERS: Listeners = Listeners::default();
+}
+
+/// The downloads shared by every window.
+pub fn downloads() -> Rc<RefCell<Downloads>> {
+    DOWNLOADS.with(Rc::clone)
+}
+
+/// Calls `f` after every change to the downloads, including progress, until
+/// it returns `ControlFlow::Break`.
+pub fn connect_downloads_changed<F: Fn() -> ControlFlow + 'static>(f: F) {
+    LISTENERS.with(|l| l.connect(f));
+}
+
+fn notify() {
+    LISTENERS.with(Listeners::notify);
+}
+
+/// Saves the downloads and notifies listeners. Call with the borrow of
+/// `downloads()` released.
+fn downloads_changed() {
+    downloads().borrow().save();
+    notify();
+}
+
+impl Downloads {
+    fn load() -> Downloads {
+        let mut entries: Vec<DownloadEntry> = load_json(&data_path(DOWNLOADS_FILE), "Downloads").unwrap_or_default();
+
+        // Transfers running when the browser quit can't be picked up again
+        for entry in entries.iter_mut().filter(|e| e.is_active()) {
+            entry.state = DownloadState::Failed;
+            entry.error = Some("Interrupted".to_string());
+        }
+
+        let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+
+        Downloads {
+            entries,
+            next_id,
+            active: HashMap::new(),
+            restarting: None,
+        }
+    }
+
+    // Runs on every state change, so a full disk only gets logged
+    fn save(&self) {
+        save_json(&data_path(DOWNLOADS_FILE), &self.entries);
+    }
+
+    /// Every download, most recent first.
+    pub fn entries(&self) -> Vec<DownloadEntry> {
+        self.entries.iter().rev().cloned().collect()
+    }
+
+    pub fn entry(&self, id: u64) -> Option<&DownloadEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    fn entry_mut(&mut self, id: u64) -> Option<&mut DownloadEntry> {
+        self.entries.iter_mut().find(|e| e.id == id)
+    }
+
+    fn add(&mut self, url: &str) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(DownloadEntry {
+            id,
+            url: url.to_string(),
+            destination: String::new(),
+            state: DownloadState::InProgress,
+            received: 0,
+            total: 0,
+            started: now(),
+            error: None,
+        });
+        id
+    }
+
+    fn is_tracked(&self, download: &Download) -> bool {
+        self.active.values().any(|d| d == download)
+    }
+
+    /// Forgets a finished download; the file is left alone.
+    pub fn remove(&mut self, id: u64) {
+        if !self.active.contains_key(&id) {
+            self.entries.retain(|e| e.id != id);
+        }
+    }
+
+    /// Forgets every download that isn't running and was started at or
+    /// after `since`.
+    pub fn clear_finished(&mut self, since: i64) {
+        let active = &self.active;
+        self.entries.retain(|e| active.contains_key(&e.id) || e.started < since);
+    }
+}
+
+/// Tracks every download started in `session`.
+pub fn start_downloads(session: &NetworkSession) {
+    session.connect_download_started(|_, download| {
+        let store = downloads();
+        let id = {
+            let mut store = store.borrow_mut();
+            if store.is_tracked(download) {
+                return;
+            }
+            match store.restarting.take() {
+                Some(id) => id,
+                None => {
+                    let url = download
+                        .request()
+                        .and_then(|r| r.uri())
+                        .map(|u| u.to_string())
+                        .unwrap_or_default();
+                    store.add(&url)
+                }
+            }
+        };
+
+        track(download, id);
+    });
+}
+
+/// Saves downloads started in a private `session` to the downloads folder
+/// without adding them to the downloads list.
+pub fn start_private_downloads(session: &NetworkSession) {
+    session.connect_download_started(|_, download| {
+        download.connect_decide_destination(|download, suggested| {
+            let path = unique_path(&download_dir(), &safe_file_name(suggested));
+            download.set_destination(&path.to_string_lossy());
+            true
+        });
+    });
+}
+
+// `name (1).ext` style path in `dir` that doesn't exist yet
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+fn download_dir() -> PathBuf {
+    glib::user_special_dir(glib::UserDirectory::Downloads).unwrap_or_else(glib::home_dir)
+}
+
+// Never let the server pick a directory
+fn safe_file_name(suggested: &str) -> String {
+    Path::new(suggested)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "download".to_string())
+}
+
+fn decide_destination(download: &Download, id: u64, suggested: &str) {
+    // A restarted download overwrites its own partial file
+    let previous = downloads()
+        .borrow()
+        .entry(id)
+        .map(|e| e.destination.clone())
+        .filter(|d| !d.is_empty());
+    if let Some(destination) = previous {
+        download.set_allow_overwrite(true);
+        download.set_destination(&destination);
+        return;
+    }
+
+    let name = safe_file_name(suggested);
+    let dir = download_dir();
+
+    let ask = settings().borrow().bool("ask-download-location");
+    if !ask {
+        download.set_destination(&unique_path(&dir, &name).to_string_lossy());
+        return;
+    }
+
+    let dialog = FileDialog::new();
+    dialog.set_title("Save file");
+    dialog.set_initial_name(Some(&name));
+    dialog.set_initial_folder(Some(&gio::File::for_path(&dir)));
+
+    let parent = download
+        .web_view()
+        .and_then(|w| w.root())
+        .and_then(|r| r.downcast::<Window>().ok());
+
+    let download = download.clone();
+    dialog.save(parent.as_ref(), None::<&gio::Cancellable>, move |result| {
+        match result.ok().and_then(|file| file.path()) {
+            Some(path) => {
+                download.set_allow_overwrite(true);
+                download.set_destination(&path.to_string_lossy());
+            }
+            None => stop_download(id, DownloadState::Cancelled),
+        }
+    });
+}
+
+fn track(download: &Download, id: u64) {
+    {
+        let store = downloads();
+        let mut store = store.borrow_mut();
+        store.active.insert(id, download.clone());
+        if let Some(entry) = store.entry_mut(id) {
+            entry.state = DownloadState::InProgress;
+            entry.received = 0;
+            entry.error = None;
+        }
+    }
+    downloads_changed();
+
+    download.connect_decide_destination(move |download, suggested| {
+        decide_destination(download, id, suggested);
+        true
+    });
+
+    download.connect_created_destination(move |_, destination| {
+        if let Some(entry) = downloads().borrow_mut().entry_mut(id) {
+            entry.destination = destination.to_string();
+        }
+        downloads_changed();
+    });
+
+    download.connect_received_data(move |download, _| {
+        let total = download.response().map(|r| r.content_length()).unwrap_or(0);
+        let received = download.received_data_length();
+
+        // Listeners redraw on every notification, so only tell them when
+        // the shown percentage changes
+        let changed = {
+            let store = downloads();
+            let mut store = store.borrow_mut();
+            let Some(entry) = store.entry_mut(id) else {
+                return;
+            };
+            let before = entry.progress().map(|p| (p * 100.0) as u32);
+            entry.received = received;
+            entry.total = total;
+            total == 0 || before != entry.progress().map(|p| (p * 100.0) as u32)
+        };
+
+        if changed {
+            notify();
+        }
+    });
+
+    download.connect_failed(move |_, error| {
+        {
+            let store = downloads();
+            let mut store = store.borrow_mut();
+            store.active.remove(&id);
+            if let Some(entry) = store.entry_mut(id) {
+                // Stopped by us rather than by the network
+                if entry.is_active() {
+                    entry.state = DownloadState::Failed;
+                    entry.error = Some(error.message().to_string());
+                }
+            }
+        }
+        downloads_changed();
+    });
+
+    // Also emitted after a failure
+    download.connect_finished(move |_| {
+        {
+            let store = downloads();
+            let mut store = store.borrow_mut();
+            store.active.remove(&id);
+            if let Some(entry) = store.entry_mut(id) {
+                if entry.is_active() {
+                    entry.state = DownloadState::Completed;
+                    if entry.total == 0 {
+                        entry.total = entry.received;
+                    }
+                }
+            }
+        }
+        downloads_changed();
+    });
+}
+
+// Stops a running download, leaving it in `state`. Paused downloads can
+// only be cancelled.
+fn stop_download(id: u64, state: DownloadState) {
+    let download = {
+        let store = downloads();
+        let mut store = store.borrow_mut();
+        if let Some(entry) = store.entry_mut(id) {
+            entry.stop(state);
+        }
+        store.active.remove(&id)
+    };
+
+    // Cancelling emits `failed` right away, so the store must not be borrowed
+    if let Some(download) = download {
+        download.cancel();
+    }
+    downloads_changed();
+}
+
+pub fn cancel_download(id: u64) {
+    stop_download(id, DownloadState::Cancelled);
+}
+
+pub fn pause_download(id: u64) {
+    stop_download(id, DownloadState::Paused);
+}
+
+/// Starts a paused, cancelled or failed download again from the beginning.
+pub fn restart_download(id: u64) {
+    let url = {
+        let store = downloads();
+        let mut store = store.borrow_mut();
+        let Some(entry) = store.entry(id).filter(|e| !e.is_active()) else {
+            return;
+        };
+        let url = entry.url.clone();
+        store.restarting = Some(id);
+        url
+    };
+
+    let Some(download) = network_session().download_uri(&url) else {
+        downloads().borrow_mut().restarting = None;
+        return;
+    };
+
+    // `download-started` may not have been emitted yet
+    let claimed = downloads().borrow_mut().restarting.take().is_none();
+    if !claimed {
+        track(&download, id);
+    }
+}
+
+fn launcher(id: u64) -> Option<FileLauncher> {
+    let store = downloads();
+    let store = store.borrow();
+    let entry = store.entry(id).filter(|e| e.state == DownloadState::Completed)?;
+    Some(FileLauncher::new(Some(&gio::File::for_path(&entry.destination))))
+}
+
+/// Opens a completed download with its default application.
+pub fn open_download(id: u64, parent: Option<&Window>) {
+    if let Some(launcher) = launcher(id) {
+        launcher.launch(parent, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                println!("Unable to open download: {}", e);
+            }
+        });
+    }
+}
+
+/// Shows a completed download in the file manager.
+pub fn show_download(id: u64, parent: Option<&Window>) {
+    if let Some(launcher) = launcher(id) {
+        launcher.open_containing_folder(parent, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                println!("Unable to show download: {}", e);
+            }
+        });
+    }
+}
+
+pub fn remove_download(id: u64) {
+    downloads().borrow_mut().remove(id);
+    downloads_changed();
+}
+
+pub fn clear_downloads() {
+    clear_downloads_since(i64::MIN);
+}
+
+pub fn clear_downloads_since(since: i64) {
+    downloads().borrow_mut().clear_finished(since);
+    downloads_changed();
+}
+
+// A button label and what clicking it does
+type RowAction<'a> = (&'a str, std::boxed::Box<dyn Fn()>);
+
+fn download_row(entry: &DownloadEntry, button: &MenuButton) -> Box {
+    let row = Box::new(Orientation::Vertical, 2);
+
+    let name = Label::new(Some(&entry.file_name()));
+    name.set_ellipsize(EllipsizeMode::Middle);
+    name.set_max_width_chars(40);
+    name.set_xalign(0.0);
+    row.append(&name);
+
+    if matches!(entry.state, DownloadState::InProgress | DownloadState::Paused) {
+        let progress = ProgressBar::new();
+        match entry.progress() {
+            Some(fraction) => progress.set_fraction(fraction),
+            None => progress.pulse(),
+        }
+        row.append(&progress);
+    }
+
+    let hbox = Box::new(Orientation::Horizontal, 5);
+    let status = Label::new(Some(&entry.status()));
+    status.add_css_class("dim-label");
+    status.set_ellipsize(EllipsizeMode::End);
+    status.set_hexpand(true);
+    status.set_xalign(0.0);
+    hbox.append(&status);
+
+    let id = entry.id;
+    let parent = button.root().and_then(|r| r.downcast::<Window>().ok());
+    let actions: Vec<RowAction> = match entry.state {
+        DownloadState::InProgress => vec![
+            ("Pause", std::boxed::Box::new(move || pause_download(id))),
+            ("Cancel", std::boxed::Box::new(move || cancel_download(id))),
+        ],
+        DownloadState::Paused => vec![
+            ("Restart", std::boxed::Box::new(move || restart_download(id))),
+            ("Cancel", std::boxed::Box::new(move || cancel_download(id))),
+        ],
+        DownloadState::Completed => {
+            let window = parent.clone();
+            vec![
+                ("Open", std::boxed::Box::new(move || open_download(id, window.as_ref()))),
+                ("Show in folder", std::boxed::Box::new(move || show_download(id, parent.as_ref()))),
+            ]
+        }
+        DownloadState::Cancelled | DownloadState::Failed => {
+            vec![("Retry", std::boxed::Box::new(move || restart_download(id)))]
+        }
+    };
+
+    for (label, action) in actions {
+        let action_button = Button::with_label(label);
+        action_button.add_css_class("flat");
+        action_button.connect_clicked(move |_| action());
+        hbox.append(&action_button);
+    }
+
+    row.append(&hbox);
+    row
+}
+
+fn fill_downloads_popover(list: &Box, button: &MenuButton) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+
+    for entry in downloads().borrow().entries().iter().take(POPOVER_DOWNLOADS) {
+        list.append(&download_row(entry, button));
+    }
+}
+
+/// Toolbar button showing the progress of recent downloads, hidden until
+/// something has been downloaded.
+pub fn create_downloads_button() -> MenuButton {
+    let button = MenuButton::new();
+    button.set_label("⇩");
+    button.set_tooltip_text(Some("Downloads"));
+
+    let vbox = Box::new(Orientation::Vertical, 10);
+    vbox.set_margin_top(5);
+    vbox.set_margin_bottom(5);
+    vbox.set_margin_start(5);
+    vbox.set_margin_end(5);
+    vbox.set_size_request(350, -1);
+
+    let list = Box::new(Orientation::Vertical, 10);
+    vbox.append(&list);
+
+    let footer = Box::new(Orientation::Horizontal, 5);
+    let show_all = Button::with_label("Show all downloads");
+    let clear = Button::with_label("Clear");
+    show_all.set_hexpand(true);
+    footer.append(&show_all);
+    footer.append(&clear);
+    vbox.append(&footer);
+
+    let popover = Popover::new();
+    popover.set_child(Some(&vbox));
+    button.set_popover(Some(&popover));
+
+    let popover_clone = popover.clone();
+    show_all.connect_clicked(move |show_all| {
+        popover_clone.popdown();
+        if let (Some(browser_window), Some(app)) = (
+            window_for_widget(show_all),
+            show_all.root().and_then(|r| r.downcast::<Window>().ok()).and_then(|w| w.application()),
+        ) {
+            create_tab("rubra://downloads", &browser_window.notebook, &app);
+        }
+    });
+
+    clear.connect_clicked(|_| clear_downloads());
+
+    let button_clone = button.clone();
+    let list_clone = list.clone();
+    popover.connect_show(move |_| {
+        fill_downloads_popover(&list_clone, &button_clone);
+    });
+
+    let weak = button.downgrade();
+    let update = move || {
+        let Some(button) = weak.upgrade() else {
+            return ControlFlow::Break;
+        };
+
+        button.set_visible(!downloads().borrow().entries.is_empty());
+        if popover.is_visible() {
+            fill_downloads_popover(&list, &button);
+        }
+        ControlFlow::Continue
+    };
+    update();
+    connect_downloads_changed(update);
+
+    button
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    fn entry(state: DownloadState) -> DownloadEntry {
+        DownloadEntry {
+            id: 1,
+            url: "http://localhost/file.bin".to_string(),
+            destination: String::new(),
+            state,
+            received: 0,
+            total: 0,
+            started: 0,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn running_downloads_can_be_paused_or_cancelled() {
+        for state in [DownloadState::Paused, DownloadState::Cancelled] {
+            let mut download = entry(DownloadState::InProgress);
+            download.stop(state);
+            assert_eq!(download.state, state);
+        }
+    }
+
+    #[test]
+    fn paused_downloads_can_only_be_cancelled() {
+        let mut download = entry(DownloadState::Paused);
+        download.stop(DownloadState::Paused);
+        assert_eq!(download.state, DownloadState::Paused);
+        download.stop(DownloadState::Cancelled);
+        assert_eq!(download.state, DownloadState::Cancelled);
+    }
+
+    #[test]
+    fn finished_downloads_stay_finished() {
+        for state in [DownloadState::Completed, DownloadState::Cancelled, DownloadState::Failed] {
+            let mut download = entry(state);
+            download.stop(DownloadState::Cancelled);
+            assert_eq!(download.state, state);
+        }
+    }
+
+    #[test]
+    fn server_names_cant_leave_the_download_dir() {
+        assert_eq!(safe_file_name("report.pdf"), "report.pdf");
+        assert_eq!(safe_file_name("../../.bashrc"), ".bashrc");
+        assert_eq!(safe_file_name("/etc/passwd"), "passwd");
+        assert_eq!(safe_file_name(""), "download");
+        assert_eq!(safe_file_name(".."), "download");
+    }
+
+    // Answers one request on a local port with `body`, returning its URL
+    fn serve_once(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    #[ignore = "needs WebKitGTK and a display"]
+    fn downloads_from_a_local_server() {
+        // Keep the downloads list, settings and the file itself out of the
+        // real home directory
+        let home = std::env::temp_dir().join(format!("rubra-download-test-{}", std::process::id()));
+        for var in ["HOME", "XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_CACHE_HOME"] {
+            std::env::set_var(var, &home);
+        }
+        fs::create_dir_all(&home).unwrap();
+        gtk4::init().unwrap();
+
+        let session = NetworkSession::new_ephemeral();
+        start_downloads(&session);
+
+        let body: &[u8] = b"A file served to the download manager test.\n";
+        let url = serve_once(body);
+        session.download_uri(&url).unwrap();
+
+        // Wakes the loop up so the deadline is checked
+        let _tick = glib::timeout_add_local(Duration::from_millis(100), || ControlFlow::Continue);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let context = glib::MainContext::default();
+        while Instant::now() < deadline && downloads().borrow().entries().first().is_none_or(|e| e.is_active()) {
+            context.iteration(true);
+        }
+
+        let entry = downloads().borrow().entries()[0].clone();
+        assert_eq!(entry.state, DownloadState::Completed, "{:?}", entry.error);
+        assert_eq!(entry.url, url);
+        assert_eq!(entry.received, body.len() as u64);
+        assert_eq!(fs::read(&entry.destination).unwrap(), body);
+
+        fs::remove_dir_all(&home).unwrap();
+    }
+}
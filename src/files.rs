@@ -0,0 +1,142 @@
+use gtk4::{prelude::*, AlertDialog, Application};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    // Problems found while loading files, until they have been shown to the user
+    static WARNINGS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Logs a problem and keeps it for `show_warnings`. A problem that is
+/// already waiting to be shown isn't repeated.
+pub fn warn(message: &str, detail: String) {
+    eprintln!("{}: {}", message, detail);
+    let warning = (message.to_string(), detail);
+    WARNINGS.with(|w| {
+        let mut warnings = w.borrow_mut();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    });
+}
+
+/// Shows the problems found so far over the active window.
+pub fn show_warnings(app: &Application) {
+    for (message, detail) in WARNINGS.with(|w| w.take()) {
+        AlertDialog::builder()
+            .message(message)
+            .detail(detail)
+            .build()
+            .show(app.active_window().as_ref());
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Copies `path` to `<path>.bak`, returning where the copy is.
+pub fn back_up(path: &Path) -> io::Result<PathBuf> {
+    let backup = with_suffix(path, ".bak");
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Reads the JSON file at `path`, `None` if there is none. A file that can't
+/// be read or parsed is backed up and reported as `what` being reset, and
+/// gives `None` as well so the caller starts over from its defaults.
+pub fn load_json<T: DeserializeOwned>(path: &Path, what: &str) -> Option<T> {
+    if !path.exists() {
+        return None;
+    }
+
+    let loaded = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()));
+    let error = match loaded {
+        Ok(value) => return Some(value),
+        Err(e) => e,
+    };
+
+    let detail = match back_up(path) {
+        Ok(backup) => format!("{}\n\n{} were reset. The old file was saved as {}.", error, what, backup.display()),
+        Err(e) => format!("{}\n\n{} were reset. The old file couldn't be backed up: {}", error, what, e),
+    };
+    warn(&format!("Unable to read {}", path.display()), detail);
+    None
+}
+
+/// Replaces `path` with `contents` through a temporary file, so a crash
+/// halfway through leaves the old file in place.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// Writes `value` to `path` as JSON. Failures are only logged, a full disk
+/// shouldn't take the browser down.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) {
+    let written = serde_json::to_string_pretty(value)
+        .map_err(|e| e.to_string())
+        .and_then(|json| write_atomic(path, &json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Unable to write to {}: {}", path.display(), e);
+    }
+}
+
+/// An empty directory for the files of one test.
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rubra-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn saves_and_loads_json() {
+        let path = test_dir("json").join("values.json");
+        assert_eq!(load_json::<BTreeMap<String, u32>>(&path, "Values"), None);
+
+        let values = BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        save_json(&path, &values);
+
+        assert_eq!(load_json(&path, "Values"), Some(values));
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    #[test]
+    fn backs_up_broken_files() {
+        let path = test_dir("broken").join("values.json");
+        fs::write(&path, "{ \"a\": ").unwrap();
+
+        assert_eq!(load_json::<BTreeMap<String, u32>>(&path, "Values"), None);
+
+        assert_eq!(fs::read_to_string(with_suffix(&path, ".bak")).unwrap(), "{ \"a\": ");
+        let warnings = WARNINGS.with(|w| w.take());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].1.contains("Values were reset"));
+    }
+
+    #[test]
+    fn replaces_files_whole() {
+        let path = test_dir("atomic").join("file.txt");
+        write_atomic(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,171 @@
+use gtk4::{prelude::*, Box, Button, CheckButton, Label, Orientation, SearchBar, SearchEntry};
+use std::cell::Cell;
+use std::rc::Rc;
+use webkit6::prelude::*;
+use webkit6::{FindController, FindOptions, WebView};
+
+const MAX_MATCHES: u32 = 1000;
+
+/// Find in page bar of a tab, shown above its web view.
+#[derive(Debug, Clone)]
+pub struct FindBar {
+    pub bar: SearchBar,
+    entry: SearchEntry,
+}
+
+impl FindBar {
+    pub fn show(&self) {
+        self.bar.set_search_mode(true);
+        self.entry.grab_focus();
+        self.entry.select_region(0, -1);
+    }
+}
+
+// WebKit doesn't tell which match is selected, so only the number of them
+// is shown
+fn update_count(label: &Label, total: u32) {
+    match total {
+        0 => label.set_text(""),
+        1 => label.set_text("1 match"),
+        total => label.set_text(&format!("{} matches", total)),
+    }
+}
+
+pub fn create_find_bar(webview: &WebView) -> FindBar {
+    let entry = SearchEntry::new();
+    entry.set_placeholder_text(Some("Find in page"));
+    entry.set_width_chars(30);
+
+    let previous = Button::from_icon_name("go-up-symbolic");
+    previous.set_tooltip_text(Some("Previous match"));
+    let next = Button::from_icon_name("go-down-symbolic");
+    next.set_tooltip_text(Some("Next match"));
+
+    let match_case = CheckButton::with_label("Match case");
+    let word_starts = CheckButton::with_label("Word starts");
+    word_starts.set_tooltip_text(Some("Only match at the start of words"));
+
+    let count = Label::new(None);
+    count.add_css_class("dim-label");
+
+    let hbox = Box::new(Orientation::Horizontal, 5);
+    hbox.append(&entry);
+    hbox.append(&previous);
+    hbox.append(&next);
+    hbox.append(&match_case);
+    hbox.append(&word_starts);
+    hbox.append(&count);
+
+    let bar = SearchBar::new();
+    bar.set_child(Some(&hbox));
+    bar.set_show_close_button(true);
+    // Lets Escape in the entry close the bar
+    bar.connect_entry(&entry);
+
+    let Some(controller) = webview.find_controller() else {
+        return FindBar { bar, entry };
+    };
+
+    let matches = Rc::new(Cell::new(0));
+
+    // Weak, as the widgets' and controller's own handlers hold these closures
+    let search = {
+        let controller = controller.downgrade();
+        let entry = entry.downgrade();
+        let matches = Rc::clone(&matches);
+        let count = count.clone();
+        let (match_case, word_starts) = (match_case.downgrade(), word_starts.downgrade());
+        move || {
+            let (Some(controller), Some(entry)) = (controller.upgrade(), entry.upgrade()) else {
+                return;
+            };
+            let text = entry.text();
+            matches.set(0);
+            update_count(&count, 0);
+            entry.remove_css_class("error");
+
+            if text.is_empty() {
+                controller.search_finish();
+                return;
+            }
+
+            let mut options = FindOptions::WRAP_AROUND;
+            if !match_case.upgrade().is_some_and(|b| b.is_active()) {
+                options |= FindOptions::CASE_INSENSITIVE;
+            }
+            if word_starts.upgrade().is_some_and(|b| b.is_active()) {
+                options |= FindOptions::AT_WORD_STARTS;
+            }
+
+            controller.count_matches(&text, options.bits(), MAX_MATCHES);
+            controller.search(&text, options.bits(), MAX_MATCHES);
+        }
+    };
+    let search = Rc::new(search);
+
+    let search_clone = Rc::clone(&search);
+    entry.connect_search_changed(move |_| search_clone());
+    for option in [&match_case, &word_starts] {
+        let search = Rc::clone(&search);
+        option.connect_toggled(move |_| search());
+    }
+
+    let step = {
+        let controller = controller.downgrade();
+        let matches = Rc::clone(&matches);
+        move |forward: bool| {
+            let Some(controller) = controller.upgrade().filter(|_| matches.get() > 0) else {
+                return;
+            };
+            if forward {
+                controller.search_next();
+            } else {
+                controller.search_previous();
+            }
+        }
+    };
+    let step = Rc::new(step);
+
+    for (button, forward) in [(&next, true), (&previous, false)] {
+        let step = Rc::clone(&step);
+        button.connect_clicked(move |_| step(forward));
+    }
+    let step_clone = Rc::clone(&step);
+    entry.connect_activate(move |_| step_clone(true));
+    let step_clone = Rc::clone(&step);
+    entry.connect_next_match(move |_| step_clone(true));
+    entry.connect_previous_match(move |_| step(false));
+
+    connect_find_results(&controller, &entry, &count, &matches);
+
+    let webview = webview.downgrade();
+    bar.connect_search_mode_enabled_notify(move |bar| {
+        if bar.is_search_mode() {
+            return;
+        }
+        controller.search_finish();
+        if let Some(webview) = webview.upgrade() {
+            webview.grab_focus();
+        }
+    });
+
+    FindBar { bar, entry }
+}
+
+fn connect_find_results(controller: &FindController, entry: &SearchEntry, count: &Label, matches: &Rc<Cell<u32>>) {
+    let (count_clone, matches_clone) = (count.clone(), Rc::clone(matches));
+    controller.connect_counted_matches(move |_, total| {
+        matches_clone.set(total);
+        update_count(&count_clone, total);
+    });
+
+    let (entry, count, matches) = (entry.downgrade(), count.clone(), Rc::clone(matches));
+    controller.connect_failed_to_find_text(move |_| {
+        let Some(entry) = entry.upgrade() else {
+            return;
+        };
+        matches.set(0);
+        count.set_text("No matches");
+        entry.add_css_class("error");
+    });
+}
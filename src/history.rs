@@ -0,0 +1,475 @@
+use gtk4::glib;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::files::write_atomic;
+use crate::paths::data_path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    Link,
+    Typed,
+    FormSubmit,
+    BackForward,
+    Reload,
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Visit {
+    pub url: String,
+    pub title: String,
+    /// Seconds since the Unix epoch.
+    pub time: i64,
+    pub transition: Transition,
+}
+
+/// All visits to one URL folded together.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+    pub last_visit: i64,
+    pub visit_count: u32,
+    pub transition: Transition,
+}
+
+// The history file is an append-only log; titles usually arrive after the
+// visit was committed, so they are recorded as separate updates.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Visit(Visit),
+    Title { url: String, title: String },
+}
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+pub struct History {
+    visits: Vec<Visit>,
+    path: PathBuf,
+}
+
+thread_local! {
+    static HISTORY: Rc<RefCell<History>> = Rc::new(RefCell::new(History::load(data_path(HISTORY_FILE))));
+}
+
+/// The history store shared by every tab.
+pub fn history() -> Rc<RefCell<History>> {
+    HISTORY.with(Rc::clone)
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Local calendar day of a timestamp, formatted as `YYYY-MM-DD`.
+pub fn day_of(time: i64) -> String {
+    glib::DateTime::from_unix_local(time)
+        .and_then(|dt| dt.format("%Y-%m-%d"))
+        .map(|s| s.to_string())
+        .unwrap_or_default()
+}
+
+/// Whether `data` reads as a history log, for files from elsewhere.
+pub fn is_history_log(data: &str) -> bool {
+    data.lines()
+        .filter(|l| !l.trim().is_empty())
+        .all(|line| serde_json::from_str::<Record>(line).is_ok())
+}
+
+fn is_recordable(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("file://")
+}
+
+impl History {
+    fn load(path: PathBuf) -> History {
+        let mut history = History { visits: Vec::new(), path };
+
+        if !history.path.exists() {
+            return history;
+        }
+
+        let data = match fs::read_to_string(&history.path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Unable to read history file: {}", e);
+                return history;
+            }
+        };
+
+        for line in data.lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str::<Record>(line) {
+                Ok(Record::Visit(visit)) => history.visits.push(visit),
+                Ok(Record::Title { url, title }) => {
+                    history.apply_title(&url, &title);
+                }
+                Err(e) => eprintln!("Skipping malformed history record: {}", e),
+            }
+        }
+
+        history
+    }
+
+    // Failing to record a visit shouldn't get in the way of browsing, so
+    // write errors are only logged
+    fn append(&self, record: &Record) {
+        let line = serde_json::to_string(record).expect("Failed to serialize history record");
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = written {
+            eprintln!("Unable to write to history file: {}", e);
+        }
+    }
+
+    // Rewrites the log with only the current visits, used after deletions.
+    fn save(&self) {
+        let mut data = String::new();
+        for visit in &self.visits {
+            let record = Record::Visit(visit.clone());
+            data.push_str(&serde_json::to_string(&record).expect("Failed to serialize history record"));
+            data.push('\n');
+        }
+        if let Err(e) = write_atomic(&self.path, &data) {
+            eprintln!("Unable to write to history file: {}", e);
+        }
+    }
+
+    fn apply_title(&mut self, url: &str, title: &str) -> bool {
+        match self.visits.iter_mut().rev().find(|v| v.url == url) {
+            Some(visit) if visit.title != title => {
+                visit.title = title.to_string();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records a committed navigation. The page title is not known yet at
+    /// that point, so the last title seen for `url` is carried over until
+    /// `set_title` is called.
+    pub fn record_visit(&mut self, url: &str, transition: Transition) {
+        if !is_recordable(url) {
+            return;
+        }
+
+        let title = self
+            .visits
+            .iter()
+            .rev()
+            .find(|v| v.url == url)
+            .map(|v| v.title.clone())
+            .unwrap_or_default();
+
+        let visit = Visit {
+            url: url.to_string(),
+            title,
+            time: now(),
+            transition,
+        };
+        self.append(&Record::Visit(visit.clone()));
+        self.visits.push(visit);
+    }
+
+    /// Updates the title of the most recent visit to `url`.
+    pub fn set_title(&mut self, url: &str, title: &str) {
+        if is_recordable(url) && self.apply_title(url, title) {
+            self.append(&Record::Title {
+                url: url.to_string(),
+                title: title.to_string(),
+            });
+        }
+    }
+
+    /// One entry per URL, most recently visited first.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = Vec::new();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+
+        for visit in &self.visits {
+            match index.get(visit.url.as_str()) {
+                Some(&i) => {
+                    let entry = &mut entries[i];
+                    entry.visit_count += 1;
+                    if visit.time >= entry.last_visit {
+                        entry.last_visit = visit.time;
+                        entry.transition = visit.transition;
+                        if !visit.title.is_empty() {
+                            entry.title = visit.title.clone();
+                        }
+                    }
+                }
+                None => {
+                    index.insert(visit.url.as_str(), entries.len());
+                    entries.push(HistoryEntry {
+                        url: visit.url.clone(),
+                        title: visit.title.clone(),
+                        last_visit: visit.time,
+                        visit_count: 1,
+                        transition: visit.transition,
+                    });
+                }
+            }
+        }
+
+        entries.sort_by_key(|e| std::cmp::Reverse(e.last_visit));
+        entries
+    }
+
+    /// Case-insensitive search over URLs and titles. Entries whose host or
+    /// title starts with the query come before plain substring matches.
+    pub fn search(&self, query: &str) -> Vec<HistoryEntry> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return self.entries();
+        }
+
+        let mut prefix = Vec::new();
+        let mut substring = Vec::new();
+
+        for entry in self.entries() {
+            let url = entry.url.to_lowercase();
+            let title = entry.title.to_lowercase();
+            let bare = strip_url_prefix(&url);
+
+            if url.starts_with(&query) || bare.starts_with(&query) || title.starts_with(&query) {
+                prefix.push(entry);
+            } else if url.contains(&query) || title.contains(&query) {
+                substring.push(entry);
+            }
+        }
+
+        prefix.extend(substring);
+        prefix
+    }
+
+    /// Visits grouped by local day, newest day and newest visit first.
+    pub fn visits_by_day(&self) -> Vec<(String, Vec<Visit>)> {
+        group_by_day(&self.visits, day_of)
+    }
+
+    pub fn visits_on_day(&self, day: &str) -> Vec<Visit> {
+        self.visits
+            .iter()
+            .rev()
+            .filter(|v| day_of(v.time) == day)
+            .cloned()
+            .collect()
+    }
+
+    /// Removes every visit to `url`.
+    pub fn delete_url(&mut self, url: &str) {
+        let before = self.visits.len();
+        self.visits.retain(|v| v.url != url);
+        if self.visits.len() != before {
+            self.save();
+        }
+    }
+
+    /// Removes a single visit, identified by its URL and time.
+    pub fn delete_visit(&mut self, url: &str, time: i64) {
+        if let Some(i) = self.visits.iter().position(|v| v.url == url && v.time == time) {
+            self.visits.remove(i);
+            self.save();
+        }
+    }
+
+    /// Removes visits with `from <= time < to`.
+    pub fn clear_range(&mut self, from: i64, to: i64) {
+        let before = self.visits.len();
+        self.visits.retain(|v| v.time < from || v.time >= to);
+        if self.visits.len() != before {
+            self.save();
+        }
+    }
+}
+
+// Groups `visits`, oldest first, by `day`, newest day and newest visit first
+fn group_by_day(visits: &[Visit], day: impl Fn(i64) -> String) -> Vec<(String, Vec<Visit>)> {
+    let mut days: Vec<(String, Vec<Visit>)> = Vec::new();
+
+    for visit in visits.iter().rev() {
+        let day = day(visit.time);
+        match days.last_mut() {
+            Some((last, visits)) if *last == day => visits.push(visit.clone()),
+            _ => days.push((day, vec![visit.clone()])),
+        }
+    }
+
+    days
+}
+
+pub(crate) fn strip_url_prefix(url: &str) -> &str {
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    url.strip_prefix("www.").unwrap_or(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::test_dir;
+
+    fn visit(url: &str, title: &str, time: i64, transition: Transition) -> Visit {
+        Visit {
+            url: url.to_string(),
+            title: title.to_string(),
+            time,
+            transition,
+        }
+    }
+
+    fn history(name: &str, visits: Vec<Visit>) -> History {
+        let history = History {
+            visits,
+            path: test_dir(name).join(HISTORY_FILE),
+        };
+        history.save();
+        history
+    }
+
+    fn times(history: &History) -> Vec<i64> {
+        history.visits.iter().map(|v| v.time).collect()
+    }
+
+    #[test]
+    fn folds_visits_by_url() {
+        let history = history(
+            "fold",
+            vec![
+                visit("https://a.com/", "A", 10, Transition::Typed),
+                visit("https://b.com/", "B", 20, Transition::Link),
+                visit("https://a.com/", "", 30, Transition::Reload),
+            ],
+        );
+
+        let entries = history.entries();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "https://a.com/");
+        assert_eq!(entries[0].visit_count, 2);
+        assert_eq!(entries[0].last_visit, 30);
+        assert_eq!(entries[0].transition, Transition::Reload);
+        // An empty title doesn't replace a known one
+        assert_eq!(entries[0].title, "A");
+        assert_eq!(entries[1].url, "https://b.com/");
+    }
+
+    #[test]
+    fn replays_the_log() {
+        let path = test_dir("replay").join(HISTORY_FILE);
+        let mut history = History::load(path.clone());
+        history.record_visit("https://example.com/", Transition::Typed);
+        history.set_title("https://example.com/", "Example");
+        history.record_visit("about:blank", Transition::Typed);
+        history.record_visit("https://example.com/", Transition::Link);
+
+        let loaded = History::load(path);
+
+        assert_eq!(loaded.visits.len(), 2);
+        assert_eq!(loaded.visits[0].title, "Example");
+        assert_eq!(loaded.visits[1].title, "Example");
+        assert_eq!(loaded.visits[1].transition, Transition::Link);
+    }
+
+    #[test]
+    fn skips_malformed_records() {
+        let path = test_dir("malformed").join(HISTORY_FILE);
+        fs::write(
+            &path,
+            "{\"op\":\"visit\",\"url\":\"https://a.com/\",\"title\":\"A\",\"time\":1,\"transition\":\"link\"}\n\
+             not json\n\
+             {\"op\":\"title\",\"url\":\"https://a.com/\",\"title\":\"New A\"}\n",
+        )
+        .unwrap();
+
+        let history = History::load(path);
+
+        assert_eq!(history.visits.len(), 1);
+        assert_eq!(history.visits[0].title, "New A");
+    }
+
+    #[test]
+    fn searches_prefixes_first() {
+        let history = history(
+            "search",
+            vec![
+                visit("https://docs.rs/rust", "Docs", 10, Transition::Link),
+                visit("https://www.rust-lang.org/", "Rust", 20, Transition::Link),
+                visit("https://example.com/", "Learning RUST", 30, Transition::Link),
+                visit("https://example.org/", "Other", 40, Transition::Link),
+            ],
+        );
+
+        let urls: Vec<_> = history.search(" Rust ").into_iter().map(|e| e.url).collect();
+
+        assert_eq!(urls, ["https://www.rust-lang.org/", "https://example.com/", "https://docs.rs/rust"]);
+        assert_eq!(history.search("").len(), 4);
+    }
+
+    #[test]
+    fn groups_visits_by_day() {
+        let day = 24 * 60 * 60;
+        let visits = vec![
+            visit("https://a.com/", "", 10, Transition::Link),
+            visit("https://b.com/", "", 20, Transition::Link),
+            visit("https://a.com/", "", day + 10, Transition::Link),
+        ];
+
+        let days = group_by_day(&visits, |time| (time / day).to_string());
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, "1");
+        assert_eq!(days[0].1.len(), 1);
+        assert_eq!(days[1].0, "0");
+        assert_eq!(days[1].1.iter().map(|v| v.time).collect::<Vec<_>>(), [20, 10]);
+    }
+
+    #[test]
+    fn clears_a_time_range() {
+        let mut history = history(
+            "range",
+            (1..=5).map(|i| visit(&format!("https://{}.com/", i), "", i * 10, Transition::Link)).collect(),
+        );
+
+        history.clear_range(20, 40);
+
+        assert_eq!(times(&history), [10, 40, 50]);
+        assert_eq!(times(&History::load(history.path.clone())), [10, 40, 50]);
+    }
+
+    #[test]
+    fn deletes_urls_and_visits() {
+        let mut history = history(
+            "delete",
+            vec![
+                visit("https://a.com/", "", 10, Transition::Link),
+                visit("https://b.com/", "", 20, Transition::Link),
+                visit("https://a.com/", "", 30, Transition::Link),
+                visit("https://b.com/", "", 40, Transition::Link),
+            ],
+        );
+
+        history.delete_url("https://a.com/");
+        assert_eq!(times(&history), [20, 40]);
+
+        history.delete_visit("https://b.com/", 40);
+        history.delete_visit("https://b.com/", 99);
+        assert_eq!(times(&history), [20]);
+        assert_eq!(times(&History::load(history.path.clone())), [20]);
+    }
+}
@@ -0,0 +1,182 @@
+use gtk4::{prelude::*, Application};
+use std::collections::{BTreeMap, HashMap};
+use crate::files::{load_json, save_json, warn};
+use crate::paths::config_path;
+
+const KEYBINDINGS_FILE: &str = "keybindings.json";
+
+/// Every action that can be bound, with its default shortcuts. The order is
+/// the priority used when two actions claim the same shortcut.
+const DEFAULT_KEYBINDINGS: &[(&str, &[&str])] = &[
+    ("win.new-tab", &["<Control>t"]),
+    ("win.new-private-window", &["<Control><Shift>n"]),
+    ("win.close-tab", &["<Control>w", "<Control>F4"]),
+    ("win.reopen-closed-tab", &["<Control><Shift>t"]),
+    ("win.next-tab", &["<Control>Page_Down", "<Control>Tab"]),
+    ("win.previous-tab", &["<Control>Page_Up", "<Control><Shift>Tab"]),
+    ("win.focus-address-bar", &["<Control>l", "<Alt>d", "F6"]),
+    ("win.reload", &["<Control>r", "F5"]),
+    ("win.hard-reload", &["<Control><Shift>r", "<Shift>F5"]),
+    ("win.back", &["<Alt>Left"]),
+    ("win.forward", &["<Alt>Right"]),
+    ("win.find", &["<Control>f"]),
+    ("win.clear-browsing-data", &["<Control><Shift>Delete"]),
+    ("win.zoom-in", &["<Control>plus", "<Control>equal"]),
+    ("win.zoom-out", &["<Control>minus"]),
+    ("win.zoom-reset", &["<Control>0"]),
+];
+
+fn default_keybindings() -> Vec<(String, Vec<String>)> {
+    DEFAULT_KEYBINDINGS
+        .iter()
+        .map(|(action, accels)| (action.to_string(), accels.iter().map(|a| a.to_string()).collect()))
+        .collect()
+}
+
+/// Reads the keymap, creating the file with the defaults on first run.
+/// Actions missing from the file keep their default shortcuts, unknown ones
+/// are added to `problems`.
+fn load_keybindings(problems: &mut Vec<String>) -> Vec<(String, Vec<String>)> {
+    let mut keybindings = default_keybindings();
+
+    let path = config_path(KEYBINDINGS_FILE);
+    if !path.exists() {
+        let map: BTreeMap<_, _> = keybindings.iter().cloned().collect();
+        save_json(&path, &map);
+        return keybindings;
+    }
+
+    let user: HashMap<String, Vec<String>> = load_json(&path, "Keyboard shortcuts").unwrap_or_default();
+    for (action, accels) in user {
+        match keybindings.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, current)) => *current = accels,
+            None => problems.push(format!("Unknown action in keybindings: {}", action)),
+        }
+    }
+
+    keybindings
+}
+
+/// Drops shortcuts `normalize` can't parse and shortcuts already taken by an
+/// earlier action, adding each to `problems`. `normalize` gives the same name
+/// for every spelling of a shortcut.
+fn resolve_conflicts(
+    keybindings: Vec<(String, Vec<String>)>,
+    normalize: impl Fn(&str) -> Option<String>,
+    problems: &mut Vec<String>,
+) -> Vec<(String, Vec<String>)> {
+    // Normalized shortcut to the action that owns it
+    let mut taken: HashMap<String, String> = HashMap::new();
+
+    keybindings
+        .into_iter()
+        .map(|(action, accels)| {
+            let accels = accels
+                .into_iter()
+                .filter(|accel| {
+                    let Some(name) = normalize(accel) else {
+                        problems.push(format!("Invalid shortcut {} for {}", accel, action));
+                        return false;
+                    };
+
+                    if let Some(owner) = taken.get(&name) {
+                        problems.push(format!(
+                            "Shortcut {} is bound to both {} and {}, keeping {}",
+                            accel, owner, action, owner
+                        ));
+                        return false;
+                    }
+
+                    taken.insert(name, action.clone());
+                    true
+                })
+                .collect();
+
+            (action, accels)
+        })
+        .collect()
+}
+
+fn accelerator_name(accel: &str) -> Option<String> {
+    let (key, mods) = gtk4::accelerator_parse(accel)?;
+    Some(gtk4::accelerator_name(key, mods).to_string())
+}
+
+pub fn apply_keybindings(app: &Application) {
+    let mut problems = Vec::new();
+    let keybindings = load_keybindings(&mut problems);
+
+    for (action, accels) in resolve_conflicts(keybindings, accelerator_name, &mut problems) {
+        let accels: Vec<&str> = accels.iter().map(|a| a.as_str()).collect();
+        app.set_accels_for_action(&action, &accels);
+    }
+
+    if !problems.is_empty() {
+        warn("Some shortcuts were not applied", problems.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for GTK's parser: modifiers in any order and case
+    fn normalize(accel: &str) -> Option<String> {
+        let mut parts: Vec<String> = accel.split('>').map(|p| p.trim_start_matches('<').to_lowercase()).collect();
+        let key = parts.pop().filter(|k| !k.is_empty())?;
+        parts.sort();
+        Some(format!("{}+{}", parts.join("+"), key))
+    }
+
+    fn bindings(bindings: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        bindings
+            .iter()
+            .map(|(action, accels)| (action.to_string(), accels.iter().map(|a| a.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn earlier_actions_keep_shared_shortcuts() {
+        let mut problems = Vec::new();
+        let resolved = resolve_conflicts(
+            bindings(&[("win.reload", &["<Control>r", "F5"]), ("win.back", &["<control>R", "<Alt>Left"])]),
+            normalize,
+            &mut problems,
+        );
+
+        assert_eq!(resolved, bindings(&[("win.reload", &["<Control>r", "F5"]), ("win.back", &["<Alt>Left"])]));
+        assert_eq!(problems, ["Shortcut <control>R is bound to both win.reload and win.back, keeping win.reload"]);
+    }
+
+    #[test]
+    fn modifier_order_doesnt_hide_conflicts() {
+        let mut problems = Vec::new();
+        let resolved = resolve_conflicts(
+            bindings(&[("win.a", &["<Control><Shift>t"]), ("win.b", &["<Shift><Control>t"])]),
+            normalize,
+            &mut problems,
+        );
+
+        assert_eq!(resolved, bindings(&[("win.a", &["<Control><Shift>t"]), ("win.b", &[])]));
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn drops_invalid_shortcuts() {
+        let mut problems = Vec::new();
+        let resolved =
+            resolve_conflicts(bindings(&[("win.find", &["<Control>", "<Control>f"])]), normalize, &mut problems);
+
+        assert_eq!(resolved, bindings(&[("win.find", &["<Control>f"])]));
+        assert_eq!(problems, ["Invalid shortcut <Control> for win.find"]);
+    }
+
+    #[test]
+    fn default_shortcuts_dont_conflict() {
+        let mut problems = Vec::new();
+        let resolved = resolve_conflicts(default_keybindings(), normalize, &mut problems);
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(resolved, default_keybindings());
+    }
+}
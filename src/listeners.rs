@@ -0,0 +1,24 @@
+use gtk4::glib::ControlFlow;
+use std::cell::RefCell;
+
+/// Callbacks run after a store changes. Each is kept until it returns
+/// `ControlFlow::Break`.
+#[derive(Default)]
+pub struct Listeners {
+    listeners: RefCell<Vec<Box<dyn Fn() -> ControlFlow>>>,
+}
+
+impl Listeners {
+    pub fn connect<F: Fn() -> ControlFlow + 'static>(&self, f: F) {
+        self.listeners.borrow_mut().push(Box::new(f));
+    }
+
+    pub fn notify(&self) {
+        // Listeners may register new listeners while they run
+        let listeners = self.listeners.take();
+        let kept: Vec<_> = listeners.into_iter().filter(|f| f() == ControlFlow::Continue).collect();
+        let mut listeners = self.listeners.borrow_mut();
+        let added = std::mem::replace(&mut *listeners, kept);
+        listeners.extend(added);
+    }
+}
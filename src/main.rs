@@ -1,43 +1,89 @@
-use gtk4::{Application, ApplicationWindow, Notebook, Settings};
+use gtk4::Application;
 use gtk4::prelude::*;
+use adblock::reload_content_blocker;
+use clear_data::clear_on_exit;
+use downloads::start_downloads;
+use files::show_warnings;
 use tab::create_tab;
+use keybindings::apply_keybindings;
+use network::network_session;
+use migration::migrate_from_cwd;
+use paths::{set_profile, DEFAULT_PROFILE};
+use profiles::{application_id, take_profile_arg, validate_profile_name};
+use pages::register_pages;
+use session::{restore_session, save_session, start_session_autosave};
+use setting::{home_page, settings};
+use window::create_window;
 
 mod tab;
 mod setting;
 mod search;
+mod history;
+mod pages;
+mod completion;
+mod bookmarks;
+mod bookmarks_io;
+mod window;
+mod session;
+mod keybindings;
+mod downloads;
+mod adblock;
+mod site_settings;
+mod paths;
+mod migration;
+mod network;
+mod profiles;
+mod site_data;
+mod clear_data;
+mod find;
+mod listeners;
+mod files;
 
 fn main() {
-    let app = Application::builder()
-        .application_id("dev.aapelix.rubra")
-        .build();
+    // --profile is handled here, everything else is left to GTK
+    let mut args: Vec<String> = std::env::args().collect();
+    let profile = take_profile_arg(&mut args).unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    if let Err(e) = validate_profile_name(&profile) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    set_profile(&profile);
 
-    app.connect_activate(|app| {
-       create_window(&app);
-    });
+    // Files in the working directory predate profiles
+    if profile == DEFAULT_PROFILE {
+        migrate_from_cwd();
+    }
 
-    app.run();
-}
-
-fn create_window(app: &Application) {
+    let app = Application::builder()
+        .application_id(application_id())
+        .build();
 
-    let settings: Settings = Settings::default().unwrap();
+    app.connect_startup(|app| {
+        register_pages();
+        apply_keybindings(app);
+        reload_content_blocker();
 
-    settings.set_gtk_application_prefer_dark_theme(true);
+        start_downloads(&network_session());
+        start_session_autosave();
+    });
 
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .default_width(1500)
-        .default_height(900)
-        .title("aapelix/rubra")
-        .resizable(true)
-        .build();
+    app.connect_activate(|app| {
+        let restore = settings().borrow().bool("restore-session");
 
-    let notebook = Notebook::new();
-    notebook.set_scrollable(true);
+        // Only restore on launch, not when activated again while running
+        if !(restore && app.windows().is_empty() && restore_session(app)) {
+            let browser_window = create_window(app, false);
+            let home = home_page();
+            create_tab(&home, &browser_window.notebook, app);
+        }
 
-    create_tab("https://start.duckduckgo.com/", &notebook, &app);
+        show_warnings(app);
+    });
 
-    window.set_child(Some(&notebook));
+    app.connect_shutdown(|_| {
+        save_session();
+        clear_on_exit();
+    });
 
-    window.present();
+    app.run_with_args(&args);
 }
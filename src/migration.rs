@@ -0,0 +1,195 @@
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::adblock::ContentBlockerConfig;
+use crate::bookmarks::Bookmarks;
+use crate::downloads::DownloadEntry;
+use crate::files::warn;
+use crate::history::is_history_log;
+use crate::paths::{config_dir, data_dir, data_path};
+use crate::search::SearchEngines;
+use crate::session::Session;
+use crate::setting::is_settings_file;
+use crate::site_settings::SiteSettings;
+
+/// Left in the data directory once the working directory has been looked
+/// at, so the files there are only taken once.
+const MIGRATION_MARKER: &str = "migrated-from-cwd";
+
+// Tells whether a file is what Rubra wrote under that name, and not
+// something else that happens to share it
+type Check = fn(&Path) -> bool;
+
+fn json<T: DeserializeOwned>(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|data| serde_json::from_str::<T>(&data).is_ok())
+}
+
+fn settings_file(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|data| is_settings_file(&data))
+}
+
+fn history_log(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|data| is_history_log(&data))
+}
+
+fn directory(path: &Path) -> bool {
+    path.is_dir()
+}
+
+fn sqlite(path: &Path) -> bool {
+    let mut header = [0; 16];
+    File::open(path).and_then(|mut file| file.read_exact(&mut header)).is_ok() && &header == b"SQLite format 3\0"
+}
+
+// Files older versions kept in the working directory, and where they live now
+fn legacy_files(config: &Path, data: &Path) -> Vec<(&'static str, PathBuf, Check)> {
+    vec![
+        ("settings.json", config.join("settings.json"), settings_file),
+        ("keybindings.json", config.join("keybindings.json"), json::<HashMap<String, Vec<String>>>),
+        ("search_engines.json", config.join("search_engines.json"), json::<SearchEngines>),
+        ("site_settings.json", config.join("site_settings.json"), json::<BTreeMap<String, SiteSettings>>),
+        ("content_blocker.json", config.join("content_blocker.json"), json::<ContentBlockerConfig>),
+        ("filters", config.join("filters"), directory),
+        ("bookmarks.json", data.join("bookmarks.json"), json::<Bookmarks>),
+        ("history.jsonl", data.join("history.jsonl"), history_log),
+        ("downloads.json", data.join("downloads.json"), json::<Vec<DownloadEntry>>),
+        ("session.json", data.join("session.json"), json::<Session>),
+        ("rubra-cookies.sqlite", data.join("cookies.sqlite"), sqlite),
+    ]
+}
+
+fn copy_dir(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &target.join(entry.file_name()))?;
+        } else {
+            fs::copy(&path, target.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+// Copies the legacy files in `source_dir` that Rubra can read, returning
+// the ones that couldn't be copied. The originals are left in place.
+fn copy_legacy_files(source_dir: &Path, files: Vec<(&'static str, PathBuf, Check)>) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for (name, target, check) in files {
+        let source = source_dir.join(name);
+        if !source.exists() || target.exists() {
+            continue;
+        }
+        if !check(&source) {
+            eprintln!("Not taking {}, it isn't a file Rubra can read", source.display());
+            continue;
+        }
+
+        let copied = if source.is_dir() {
+            copy_dir(&source, &target)
+        } else {
+            fs::copy(&source, &target).map(|_| ())
+        };
+
+        match copied {
+            Ok(()) => eprintln!("Copied {} to {}", source.display(), target.display()),
+            Err(e) => failures.push(format!("Unable to copy {} to {}: {}", name, target.display(), e)),
+        }
+    }
+
+    failures
+}
+
+/// Copies files left in the working directory by older versions into the
+/// XDG directories, the first time this version runs. Files already in the
+/// new location are left alone.
+pub fn migrate_from_cwd() {
+    let marker = data_path(MIGRATION_MARKER);
+    if marker.exists() {
+        return;
+    }
+
+    let failures = copy_legacy_files(Path::new("."), legacy_files(&config_dir(), &data_dir()));
+    if !failures.is_empty() {
+        warn(
+            "Unable to copy files from older versions",
+            format!("{}\n\nThey stay in the current directory and are not used.", failures.join("\n")),
+        );
+    }
+
+    if let Err(e) = fs::write(&marker, "") {
+        eprintln!("Unable to write {}: {}", marker.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::test_dir;
+
+    fn dirs(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = test_dir(name);
+        let (cwd, config, data) = (dir.join("cwd"), dir.join("config"), dir.join("data"));
+        for dir in [&cwd, &config, &data] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        (cwd, config, data)
+    }
+
+    #[test]
+    fn copies_files_rubra_can_read() {
+        let (cwd, config, data) = dirs("migrate");
+        let bookmarks = serde_json::to_string(&Bookmarks::new()).unwrap();
+        fs::write(cwd.join("bookmarks.json"), &bookmarks).unwrap();
+        fs::write(cwd.join("settings.json"), r#"{ "version": 2, "values": {} }"#).unwrap();
+        fs::write(cwd.join("history.jsonl"), "").unwrap();
+        fs::write(cwd.join("rubra-cookies.sqlite"), b"SQLite format 3\0 and the rest").unwrap();
+        fs::create_dir_all(cwd.join("filters/lists")).unwrap();
+        fs::write(cwd.join("filters/lists/easylist.txt"), "||ads.example^").unwrap();
+
+        let failures = copy_legacy_files(&cwd, legacy_files(&config, &data));
+
+        assert!(failures.is_empty());
+        assert_eq!(fs::read_to_string(data.join("bookmarks.json")).unwrap(), bookmarks);
+        assert!(config.join("settings.json").exists());
+        assert!(data.join("history.jsonl").exists());
+        assert!(data.join("cookies.sqlite").exists());
+        assert_eq!(fs::read_to_string(config.join("filters/lists/easylist.txt")).unwrap(), "||ads.example^");
+        // The originals stay for older versions
+        assert!(cwd.join("bookmarks.json").exists());
+        assert!(cwd.join("filters/lists/easylist.txt").exists());
+    }
+
+    #[test]
+    fn leaves_other_files_alone() {
+        let (cwd, config, data) = dirs("migrate-foreign");
+        fs::write(cwd.join("settings.json"), r#"{ "editor.fontSize": 14 }"#).unwrap();
+        fs::write(cwd.join("downloads.json"), "[1, 2, 3]").unwrap();
+        fs::write(cwd.join("history.jsonl"), "{\"op\": \"visit\"}\n").unwrap();
+        fs::write(cwd.join("rubra-cookies.sqlite"), "cookies").unwrap();
+        fs::write(cwd.join("filters"), "not a directory").unwrap();
+
+        let failures = copy_legacy_files(&cwd, legacy_files(&config, &data));
+
+        assert!(failures.is_empty());
+        assert_eq!(fs::read_dir(&config).unwrap().count(), 0);
+        assert_eq!(fs::read_dir(&data).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn keeps_files_already_moved() {
+        let (cwd, config, data) = dirs("migrate-existing");
+        let old = serde_json::to_string(&Session { windows: Vec::new() }).unwrap();
+        fs::write(cwd.join("session.json"), old).unwrap();
+        fs::write(data.join("session.json"), "current").unwrap();
+
+        copy_legacy_files(&cwd, legacy_files(&config, &data));
+
+        assert_eq!(fs::read_to_string(data.join("session.json")).unwrap(), "current");
+    }
+}
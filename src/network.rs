@@ -0,0 +1,98 @@
+use gtk4::glib::{self, ControlFlow};
+use gtk4::gio;
+use std::cell::RefCell;
+use webkit6::{CookieAcceptPolicy, CookiePersistentStorage, NetworkSession, WebsiteDataTypes};
+
+use crate::downloads::start_private_downloads;
+use crate::files::warn;
+use crate::paths::{cache_dir, data_dir, data_path};
+use crate::setting::{connect_settings_changed, settings};
+
+const COOKIES_FILE: &str = "cookies.sqlite";
+
+thread_local! {
+    static SESSION: NetworkSession = create_network_session();
+    static PRIVATE_SESSION: RefCell<Option<NetworkSession>> = const { RefCell::new(None) };
+}
+
+fn create_network_session() -> NetworkSession {
+    let data_dir = data_dir().join("website-data");
+    let cache_dir = cache_dir().join("website-data");
+    let session = NetworkSession::new(data_dir.to_str(), cache_dir.to_str());
+
+    if let Some(cookies) = session.cookie_manager() {
+        let path = data_path(COOKIES_FILE);
+        // WebKit takes UTF-8 paths only, and a lossy one would be another file
+        match path.to_str() {
+            Some(path) => cookies.set_persistent_storage(path, CookiePersistentStorage::Sqlite),
+            None => warn(
+                "Cookies won't be saved",
+                format!("{} isn't valid UTF-8, so cookies are only kept until Rubra closes.", path.display()),
+            ),
+        }
+    }
+
+    if let Some(data_manager) = session.website_data_manager() {
+        data_manager.set_favicons_enabled(true);
+    }
+
+    apply_cookie_policy(&session);
+    connect_settings_changed(|| {
+        apply_cookie_policy(&network_session());
+        if let Some(session) = PRIVATE_SESSION.with(|s| s.borrow().clone()) {
+            apply_cookie_policy(&session);
+        }
+        ControlFlow::Continue
+    });
+
+    session
+}
+
+fn apply_cookie_policy(session: &NetworkSession) {
+    let policy = match settings().borrow().get("cookie-accept-policy").as_str() {
+        "always" => CookieAcceptPolicy::Always,
+        "never" => CookieAcceptPolicy::Never,
+        _ => CookieAcceptPolicy::NoThirdParty,
+    };
+
+    if let Some(cookies) = session.cookie_manager() {
+        cookies.set_accept_policy(policy);
+    }
+}
+
+/// The network session shared by every tab, storing website data in the
+/// data and cache directories.
+pub fn network_session() -> NetworkSession {
+    SESSION.with(|s| s.clone())
+}
+
+/// The in-memory session shared by private windows, created when the first
+/// of them opens.
+pub fn private_network_session() -> NetworkSession {
+    PRIVATE_SESSION.with(|s| {
+        s.borrow_mut()
+            .get_or_insert_with(|| {
+                let session = NetworkSession::new_ephemeral();
+                apply_cookie_policy(&session);
+                start_private_downloads(&session);
+                session
+            })
+            .clone()
+    })
+}
+
+/// Wipes the private session after its last window has closed. The next
+/// private window starts a new one.
+pub fn end_private_session() {
+    let Some(session) = PRIVATE_SESSION.with(|s| s.borrow_mut().take()) else {
+        return;
+    };
+
+    if let Some(data_manager) = session.website_data_manager() {
+        data_manager.clear(WebsiteDataTypes::ALL, glib::TimeSpan(0), None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                println!("Unable to clear private browsing data: {}", e);
+            }
+        });
+    }
+}
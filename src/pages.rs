@@ -0,0 +1,315 @@
+use gtk4::glib::{self, ControlFlow};
+use gtk4::gio;
+use url::Url;
+use webkit6::prelude::*;
+use webkit6::{URISchemeRequest, WebContext, WebView};
+
+use crate::downloads::{
+    cancel_download, clear_downloads, connect_downloads_changed, downloads, open_download, pause_download,
+    remove_download, restart_download, show_download, DownloadState,
+};
+use crate::history::{day_of, history, now, Visit};
+
+const STYLE: &str = "
+body { font-family: sans-serif; background: #1e1e1e; color: #ddd; margin: 2em auto; max-width: 60em; }
+a { color: #8ab4f8; text-decoration: none; }
+h2 { border-bottom: 1px solid #444; padding-bottom: 0.2em; margin-top: 1.5em; }
+table { width: 100%; border-collapse: collapse; }
+td { padding: 0.2em 0.5em; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; max-width: 40em; }
+.time, .remove { width: 1%; color: #999; }
+.url { color: #999; }
+form { display: inline-block; margin-right: 1em; }
+progress { width: 100%; }
+.actions a { margin-left: 0.8em; }
+";
+
+thread_local! {
+    // Carried by the pages' action links. Other local pages, such as file://
+    // ones, can link to rubra:// but can't read the token from our pages.
+    static TOKEN: String = glib::uuid_string_random().to_string();
+}
+
+fn token() -> String {
+    TOKEN.with(String::clone)
+}
+
+// Whether an action request came from one of our own pages
+fn has_token(url: &Url) -> bool {
+    query_param(url, "token") == Some(token())
+}
+
+/// Registers the `rubra://` scheme for built-in pages. Must be called once,
+/// before any `WebView` loads one of them.
+pub fn register_pages() {
+    let context = WebContext::default().expect("no default web context");
+
+    // Local schemes can't be loaded by web content, so sites can't link to
+    // the pages at all
+    if let Some(security) = context.security_manager() {
+        security.register_uri_scheme_as_local("rubra");
+    }
+
+    context.register_uri_scheme("rubra", handle_request);
+}
+
+fn handle_request(request: &URISchemeRequest) {
+    let uri = request.uri().map(|u| u.to_string()).unwrap_or_default();
+
+    let html = match Url::parse(&uri) {
+        Ok(url) => match url.host_str() {
+            Some("history") => history_page(&url),
+            Some("downloads") => downloads_page(&url),
+            _ => not_found_page(&uri),
+        },
+        Err(_) => not_found_page(&uri),
+    };
+
+    let bytes = glib::Bytes::from_owned(html.into_bytes());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    request.finish(&stream, bytes.len() as i64, Some("text/html"));
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>{}</style></head><body>{}</body></html>",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+fn redirect_page(target: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta http-equiv=\"refresh\" content=\"0; url={}\"></head></html>",
+        escape(target)
+    )
+}
+
+fn not_found_page(uri: &str) -> String {
+    page("Not found", &format!("<h1>Not found</h1><p>{} is not a rubra page.</p>", escape(uri)))
+}
+
+// Unix time range covered by a `YYYY-MM-DD` local day
+fn day_range(day: &str) -> Option<(i64, i64)> {
+    let mut parts = day.splitn(3, '-').map(|p| p.parse::<i32>().ok());
+    let (year, month, date) = (parts.next()??, parts.next()??, parts.next()??);
+
+    let start = glib::DateTime::from_local(year, month, date, 0, 0, 0.0).ok()?;
+    let end = start.add_days(1).ok()?;
+    Some((start.to_unix(), end.to_unix()))
+}
+
+fn history_page(url: &Url) -> String {
+    let query = query_param(url, "q").unwrap_or_default();
+    let back = if query.is_empty() {
+        "rubra://history".to_string()
+    } else {
+        format!("rubra://history?q={}", urlencoding::encode(&query))
+    };
+
+    match url.path() {
+        "/delete" | "/clear" if !has_token(url) => return redirect_page(&back),
+        "/delete" => {
+            let target = query_param(url, "url").unwrap_or_default();
+            match query_param(url, "time").and_then(|t| t.parse::<i64>().ok()) {
+                Some(time) => history().borrow_mut().delete_visit(&target, time),
+                None => history().borrow_mut().delete_url(&target),
+            }
+            return redirect_page(&back);
+        }
+        "/clear" => {
+            let range = match query_param(url, "range").as_deref() {
+                Some("hour") => Some((now() - 60 * 60, i64::MAX)),
+                Some("day") => Some((now() - 24 * 60 * 60, i64::MAX)),
+                Some("week") => Some((now() - 7 * 24 * 60 * 60, i64::MAX)),
+                Some("all") => Some((i64::MIN, i64::MAX)),
+                _ => query_param(url, "day").and_then(|day| day_range(&day)),
+            };
+            if let Some((from, to)) = range {
+                history().borrow_mut().clear_range(from, to);
+            }
+            return redirect_page(&back);
+        }
+        _ => {}
+    }
+
+    let history = history();
+    let history = history.borrow();
+
+    let days: Vec<(String, Vec<Visit>)> = if let Some(day) = query_param(url, "day") {
+        vec![(day.clone(), history.visits_on_day(&day))]
+    } else if query.is_empty() {
+        history.visits_by_day()
+    } else {
+        let matches: Vec<String> = history.search(&query).into_iter().map(|e| e.url).collect();
+        history
+            .visits_by_day()
+            .into_iter()
+            .map(|(day, visits)| (day, visits.into_iter().filter(|v| matches.contains(&v.url)).collect::<Vec<_>>()))
+            .filter(|(_, visits)| !visits.is_empty())
+            .collect()
+    };
+
+    let mut body = String::from("<h1>History</h1>");
+
+    body.push_str(&format!(
+        "<form action=\"rubra://history\"><input type=\"search\" name=\"q\" value=\"{}\" placeholder=\"Search history\" autofocus> <button>Search</button></form>",
+        escape(&query)
+    ));
+    let token = token();
+    body.push_str(&format!(
+        "<form action=\"rubra://history/clear\"><input type=\"hidden\" name=\"token\" value=\"{}\">\
+         <select name=\"range\">\
+         <option value=\"hour\">Last hour</option>\
+         <option value=\"day\">Last 24 hours</option>\
+         <option value=\"week\">Last 7 days</option>\
+         <option value=\"all\">All time</option>\
+         </select> <button>Clear</button></form>",
+        token
+    ));
+
+    if days.is_empty() {
+        body.push_str("<p>No history.</p>");
+    }
+
+    let today = day_of(now());
+
+    for (day, visits) in &days {
+        let heading = if *day == today { "Today".to_string() } else { day.clone() };
+        body.push_str(&format!(
+            "<h2>{} <small><a href=\"rubra://history/clear?day={}&token={}\">remove day</a></small></h2><table>",
+            escape(&heading),
+            urlencoding::encode(day),
+            token
+        ));
+
+        for visit in visits {
+            let time = glib::DateTime::from_unix_local(visit.time)
+                .and_then(|dt| dt.format("%H:%M"))
+                .map(|t| t.to_string())
+                .unwrap_or_default();
+            let title = if visit.title.is_empty() { &visit.url } else { &visit.title };
+
+            body.push_str(&format!(
+                "<tr><td class=\"time\">{}</td><td><a href=\"{}\">{}</a> <span class=\"url\">{}</span></td>\
+                 <td class=\"remove\"><a href=\"rubra://history/delete?url={}&time={}&q={}&token={}\" \
+                 title=\"Remove visit\">✕</a></td></tr>",
+                time,
+                escape(&visit.url),
+                escape(title),
+                escape(&visit.url),
+                urlencoding::encode(&visit.url),
+                visit.time,
+                urlencoding::encode(&query),
+                token
+            ));
+        }
+
+        body.push_str("</table>");
+    }
+
+    page("History", &body)
+}
+
+fn downloads_page(url: &Url) -> String {
+    let id = query_param(url, "id").and_then(|id| id.parse::<u64>().ok());
+
+    if !matches!(url.path(), "" | "/") {
+        match (url.path(), id) {
+            _ if !has_token(url) => {}
+            ("/clear", _) => clear_downloads(),
+            ("/cancel", Some(id)) => cancel_download(id),
+            ("/pause", Some(id)) => pause_download(id),
+            ("/restart" | "/retry", Some(id)) => restart_download(id),
+            ("/open", Some(id)) => open_download(id, None),
+            ("/show", Some(id)) => show_download(id, None),
+            ("/remove", Some(id)) => remove_download(id),
+            _ => {}
+        }
+        return redirect_page("rubra://downloads");
+    }
+
+    let body = format!("<h1>Downloads</h1><div id=\"downloads\">{}</div>", downloads_list());
+    page("Downloads", &body)
+}
+
+// The part of rubra://downloads that changes with the downloads
+fn downloads_list() -> String {
+    let entries = downloads().borrow().entries();
+    if entries.is_empty() {
+        return "<p>No downloads.</p>".to_string();
+    }
+
+    let token = token();
+    let mut body =
+        format!("<p><a href=\"rubra://downloads/clear?token={}\">Clear finished downloads</a></p><table>", token);
+
+    for entry in &entries {
+        let progress = match (entry.state, entry.progress()) {
+            (DownloadState::InProgress | DownloadState::Paused, Some(fraction)) => {
+                format!("<progress value=\"{:.3}\"></progress>", fraction)
+            }
+            (DownloadState::InProgress, None) => "<progress></progress>".to_string(),
+            _ => String::new(),
+        };
+
+        let actions: &[(&str, &str)] = match entry.state {
+            DownloadState::InProgress => &[("pause", "Pause"), ("cancel", "Cancel")],
+            DownloadState::Paused => &[("restart", "Restart"), ("cancel", "Cancel")],
+            DownloadState::Completed => &[("open", "Open"), ("show", "Show in folder"), ("remove", "Remove")],
+            DownloadState::Cancelled | DownloadState::Failed => &[("retry", "Retry"), ("remove", "Remove")],
+        };
+        let links: String = actions
+            .iter()
+            .map(|(path, label)| {
+                format!("<a href=\"rubra://downloads/{}?id={}&token={}\">{}</a>", path, entry.id, token, label)
+            })
+            .collect();
+
+        body.push_str(&format!(
+            "<tr><td><strong>{}</strong><br><span class=\"url\">{}</span>{}<br>{}</td><td class=\"actions\">{}</td></tr>",
+            escape(&entry.file_name()),
+            escape(&entry.url),
+            progress,
+            escape(&entry.status()),
+            links
+        ));
+    }
+
+    body.push_str("</table>");
+    body
+}
+
+/// Keeps rubra://downloads in `webview` current as downloads change, by
+/// replacing the list rather than reloading the page.
+pub fn connect_downloads_page(webview: &WebView) {
+    let webview = webview.downgrade();
+    connect_downloads_changed(move || {
+        let Some(webview) = webview.upgrade() else {
+            return ControlFlow::Break;
+        };
+        if webview.uri().is_some_and(|uri| uri.starts_with("rubra://downloads")) {
+            // A JSON string is also a JavaScript string literal
+            let html = serde_json::to_string(&downloads_list()).expect("Failed to serialize downloads list");
+            let script = format!(
+                "const list = document.getElementById('downloads'); if (list) list.innerHTML = {};",
+                html
+            );
+            webview.evaluate_javascript(&script, None, None, None::<&gio::Cancellable>, |_| {});
+        }
+        ControlFlow::Continue
+    });
+}
@@ -0,0 +1,90 @@
+use gtk4::glib;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "rubra";
+const PROFILES_DIR: &str = "profiles";
+
+/// The profile whose files live directly in the rubra directories, where
+/// they were before profiles existed.
+pub const DEFAULT_PROFILE: &str = "default";
+
+thread_local! {
+    static PROFILE: RefCell<String> = RefCell::new(DEFAULT_PROFILE.to_string());
+}
+
+/// Selects the profile all paths point into. Call before anything is loaded.
+pub fn set_profile(name: &str) {
+    PROFILE.with(|p| *p.borrow_mut() = name.to_string());
+}
+
+pub fn profile() -> String {
+    PROFILE.with(|p| p.borrow().clone())
+}
+
+/// Directory of `profile` under one of the XDG base directories.
+pub fn profile_dir(base: &Path, profile: &str) -> PathBuf {
+    let dir = base.join(APP_DIR);
+    if profile == DEFAULT_PROFILE {
+        dir
+    } else {
+        dir.join(PROFILES_DIR).join(profile)
+    }
+}
+
+/// The config, data and cache base directories.
+pub fn base_dirs() -> [PathBuf; 3] {
+    [glib::user_config_dir(), glib::user_data_dir(), glib::user_cache_dir()]
+}
+
+/// Names of the profiles other than the default one.
+pub fn named_profiles() -> Vec<String> {
+    let Ok(dir) = fs::read_dir(glib::user_config_dir().join(APP_DIR).join(PROFILES_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = dir
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+fn app_dir(base: PathBuf) -> PathBuf {
+    let dir = profile_dir(&base, &profile());
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Unable to create {}: {}", dir.display(), e);
+    }
+    dir
+}
+
+/// `$XDG_CONFIG_HOME/rubra`, for files the user may edit by hand. Named
+/// profiles use `profiles/<name>` inside it, as do the other directories.
+pub fn config_dir() -> PathBuf {
+    app_dir(glib::user_config_dir())
+}
+
+/// `$XDG_DATA_HOME/rubra`, for state the browser keeps.
+pub fn data_dir() -> PathBuf {
+    app_dir(glib::user_data_dir())
+}
+
+/// `$XDG_CACHE_HOME/rubra`, for anything that can be rebuilt.
+pub fn cache_dir() -> PathBuf {
+    app_dir(glib::user_cache_dir())
+}
+
+pub fn config_path(name: &str) -> PathBuf {
+    config_dir().join(name)
+}
+
+pub fn data_path(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+pub fn cache_path(name: &str) -> PathBuf {
+    cache_dir().join(name)
+}
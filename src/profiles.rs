@@ -0,0 +1,303 @@
+use gtk4::{
+    gio, glib, prelude::*, AlertDialog, Application, ApplicationWindow, Box, Button, Entry, ListBox, ListBoxRow,
+    Orientation, ScrolledWindow,
+};
+use std::fs;
+use std::process::Command;
+
+use crate::paths::{base_dirs, named_profiles, profile, profile_dir, DEFAULT_PROFILE};
+
+const APPLICATION_ID: &str = "dev.aapelix.rubra";
+
+/// Removes `--profile NAME` or `--profile=NAME` from the command line,
+/// returning the name.
+pub fn take_profile_arg(args: &mut Vec<String>) -> Option<String> {
+    let index = args.iter().position(|a| a == "--profile" || a.starts_with("--profile="))?;
+    let arg = args.remove(index);
+    match arg.strip_prefix("--profile=") {
+        Some(name) => Some(name.to_string()),
+        None if index < args.len() => Some(args.remove(index)),
+        None => None,
+    }
+}
+
+/// Each profile is its own application, so profiles can run side by side.
+pub fn application_id() -> String {
+    profile_application_id(&profile())
+}
+
+fn profile_application_id(name: &str) -> String {
+    match name {
+        DEFAULT_PROFILE => APPLICATION_ID.to_string(),
+        name => format!("{}.profile_{}", APPLICATION_ID, name),
+    }
+}
+
+/// Whether another browser process has `name` open, going by its
+/// application's name on the session bus.
+pub fn is_profile_running(name: &str) -> bool {
+    let Ok(connection) = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>) else {
+        return false;
+    };
+
+    connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+            Some(&(profile_application_id(name),).to_variant()),
+            Some(glib::VariantTy::new("(b)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )
+        .ok()
+        .and_then(|reply| reply.get::<(bool,)>())
+        .is_some_and(|(running,)| running)
+}
+
+pub fn profiles() -> Vec<String> {
+    let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+    profiles.extend(named_profiles());
+    profiles
+}
+
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Profile names can't be empty".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Profile names may only contain letters, digits, - and _".to_string());
+    }
+    Ok(())
+}
+
+pub fn create_profile(name: &str) -> Result<(), String> {
+    validate_profile_name(name)?;
+    if profiles().iter().any(|p| p == name) {
+        return Err(format!("A profile called {} already exists", name));
+    }
+
+    for base in base_dirs() {
+        fs::create_dir_all(profile_dir(&base, name)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+pub fn rename_profile(from: &str, to: &str) -> Result<(), String> {
+    if from == DEFAULT_PROFILE || from == profile() {
+        return Err(format!("The {} profile can't be renamed", from));
+    }
+    if is_profile_running(from) {
+        return Err(format!("The {} profile is open, close its windows first", from));
+    }
+    validate_profile_name(to)?;
+    if profiles().iter().any(|p| p == to) {
+        return Err(format!("A profile called {} already exists", to));
+    }
+
+    for base in base_dirs() {
+        let dir = profile_dir(&base, from);
+        if dir.exists() {
+            fs::rename(&dir, profile_dir(&base, to)).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn delete_profile(name: &str) -> Result<(), String> {
+    if name == DEFAULT_PROFILE || name == profile() {
+        return Err(format!("The {} profile can't be deleted", name));
+    }
+    if is_profile_running(name) {
+        return Err(format!("The {} profile is open, close its windows first", name));
+    }
+
+    for base in base_dirs() {
+        let dir = profile_dir(&base, name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Starts a new browser process for `name`.
+pub fn open_profile(name: &str) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    Command::new(exe).arg("--profile").arg(name).spawn().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn show_error(window: &ApplicationWindow, message: &str, error: String) {
+    AlertDialog::builder().message(message).detail(error).build().show(Some(window));
+}
+
+fn profile_rows(list: &ListBox, window: &ApplicationWindow) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+
+    let current = profile();
+
+    for name in profiles() {
+        let row = ListBoxRow::new();
+        let hbox = Box::new(Orientation::Horizontal, 10);
+
+        let entry = Entry::new();
+        entry.set_text(&name);
+        entry.set_hexpand(true);
+        hbox.append(&entry);
+
+        let open = Button::with_label(if name == current { "Current" } else { "Open" });
+        let rename = Button::with_label("Rename");
+        let delete = Button::with_label("Delete");
+        hbox.append(&open);
+        hbox.append(&rename);
+        hbox.append(&delete);
+
+        open.set_sensitive(name != current);
+        // The default profile's files have no directory of their own to move
+        let fixed = name == current || name == DEFAULT_PROFILE;
+        entry.set_sensitive(!fixed);
+        rename.set_sensitive(!fixed);
+        delete.set_sensitive(!fixed);
+
+        let name_clone = name.clone();
+        let window_clone = window.clone();
+        open.connect_clicked(move |_| {
+            if let Err(e) = open_profile(&name_clone) {
+                show_error(&window_clone, "Unable to open profile", e);
+            }
+        });
+
+        let name_clone = name.clone();
+        let window_clone = window.clone();
+        let list_clone = list.clone();
+        let entry_clone = entry.clone();
+        rename.connect_clicked(move |_| {
+            match rename_profile(&name_clone, entry_clone.text().trim()) {
+                Ok(()) => profile_rows(&list_clone, &window_clone),
+                Err(e) => show_error(&window_clone, "Unable to rename profile", e),
+            }
+        });
+
+        let window_clone = window.clone();
+        let list_clone = list.clone();
+        delete.connect_clicked(move |_| {
+            let dialog = AlertDialog::builder()
+                .message(format!("Delete the profile {}?", name))
+                .detail("Its settings, bookmarks, history and cookies will be lost.")
+                .buttons(["Cancel", "Delete"])
+                .cancel_button(0)
+                .default_button(0)
+                .build();
+
+            let name = name.clone();
+            let window = window_clone.clone();
+            let list = list_clone.clone();
+            dialog.choose(Some(&window_clone), None::<&gio::Cancellable>, move |result| {
+                if !matches!(result, Ok(1)) {
+                    return;
+                }
+                match delete_profile(&name) {
+                    Ok(()) => profile_rows(&list, &window),
+                    Err(e) => show_error(&window, "Unable to delete profile", e),
+                }
+            });
+        });
+
+        row.set_child(Some(&hbox));
+        list.append(&row);
+    }
+}
+
+pub fn create_profiles_window(application: &Application) {
+    let window = ApplicationWindow::new(application);
+    window.set_title(Some("aapelix/rubra/profiles"));
+    window.set_default_size(600, 400);
+
+    let content = Box::new(Orientation::Vertical, 10);
+    let toolbar = Box::new(Orientation::Horizontal, 10);
+
+    let new_name = Entry::new();
+    new_name.set_placeholder_text(Some("New profile name"));
+    new_name.set_hexpand(true);
+    toolbar.append(&new_name);
+
+    let create = Button::with_label("Create");
+    toolbar.append(&create);
+
+    let list = ListBox::new();
+    let scrolled_window = ScrolledWindow::new();
+    scrolled_window.set_vexpand(true);
+    scrolled_window.set_child(Some(&list));
+
+    profile_rows(&list, &window);
+
+    let window_clone = window.clone();
+    let list_clone = list.clone();
+    create.connect_clicked(move |_| {
+        match create_profile(new_name.text().trim()) {
+            Ok(()) => {
+                new_name.set_text("");
+                profile_rows(&list_clone, &window_clone);
+            }
+            Err(e) => show_error(&window_clone, "Unable to create profile", e),
+        }
+    });
+
+    content.append(&toolbar);
+    content.append(&scrolled_window);
+
+    window.set_child(Some(&content));
+    window.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn takes_the_profile_argument() {
+        let mut separate = args(&["rubra", "--profile", "work", "https://example.com"]);
+        assert_eq!(take_profile_arg(&mut separate).as_deref(), Some("work"));
+        assert_eq!(separate, args(&["rubra", "https://example.com"]));
+
+        let mut joined = args(&["rubra", "--profile=work"]);
+        assert_eq!(take_profile_arg(&mut joined).as_deref(), Some("work"));
+        assert_eq!(joined, args(&["rubra"]));
+    }
+
+    #[test]
+    fn missing_profile_arguments() {
+        let mut none = args(&["rubra", "https://example.com"]);
+        assert_eq!(take_profile_arg(&mut none), None);
+        assert_eq!(none, args(&["rubra", "https://example.com"]));
+
+        let mut dangling = args(&["rubra", "--profile"]);
+        assert_eq!(take_profile_arg(&mut dangling), None);
+        assert_eq!(dangling, args(&["rubra"]));
+    }
+
+    #[test]
+    fn validates_profile_names() {
+        for name in ["work", "a-b", "a_b", "Profile2"] {
+            assert!(validate_profile_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "a b", "../work", "work/x", "wörk", "a.b"] {
+            assert!(validate_profile_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn profiles_get_distinct_application_ids() {
+        assert_eq!(profile_application_id(DEFAULT_PROFILE), APPLICATION_ID);
+        assert_ne!(profile_application_id("a-b"), profile_application_id("a_b"));
+    }
+}
@@ -1,18 +1,121 @@
 use url::Url;
 use regex::Regex;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::files::{load_json, save_json};
+use crate::paths::config_path;
 
 lazy_static! {
     static ref DOMAIN_PATTERN: Regex = Regex::new(r"^[a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(\.[a-zA-Z]{2,})+$").unwrap();
 }
 
-pub fn process_search_input(input: &str) -> String {
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchEngine {
+    pub name: String,
+    /// Search URL template, `%s` is replaced with the encoded query.
+    pub url: String,
+    pub keyword: String,
+    /// Page opened for new tabs while this engine is the default.
+    pub home: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchEngines {
+    pub default: String,
+    pub engines: Vec<SearchEngine>,
+}
+
+const SEARCH_ENGINES_FILE: &str = "search_engines.json";
+
+impl SearchEngine {
+    pub fn search_url(&self, query: &str) -> String {
+        self.url.replace("%s", &urlencoding::encode(query))
+    }
+}
+
+impl SearchEngines {
+    pub fn default_engine(&self) -> Option<&SearchEngine> {
+        self.engines
+            .iter()
+            .find(|e| e.name == self.default)
+            .or_else(|| self.engines.first())
+    }
+
+    pub fn by_keyword(&self, keyword: &str) -> Option<&SearchEngine> {
+        self.engines.iter().find(|e| !e.keyword.is_empty() && e.keyword == keyword)
+    }
+
+    pub fn home_url(&self) -> String {
+        self.default_engine()
+            .map(|e| e.home.clone())
+            .unwrap_or_else(|| "about:blank".to_string())
+    }
+}
+
+fn default_search_engines() -> SearchEngines {
+    let engine = |name: &str, url: &str, keyword: &str, home: &str| SearchEngine {
+        name: name.to_string(),
+        url: url.to_string(),
+        keyword: keyword.to_string(),
+        home: home.to_string(),
+    };
+
+    SearchEngines {
+        default: "DuckDuckGo".to_string(),
+        engines: vec![
+            engine("DuckDuckGo", "https://duckduckgo.com/?q=%s", "d", "https://start.duckduckgo.com/"),
+            engine("Google", "https://www.google.com/search?q=%s", "g", "https://www.google.com/"),
+            engine("Wikipedia", "https://en.wikipedia.org/w/index.php?search=%s", "w", "https://en.wikipedia.org/"),
+            engine("GitHub", "https://github.com/search?q=%s", "gh", "https://github.com/"),
+        ],
+    }
+}
+
+thread_local! {
+    static SEARCH_ENGINES: Rc<RefCell<SearchEngines>> = Rc::new(RefCell::new(load_search_engines()));
+}
+
+fn load_search_engines() -> SearchEngines {
+    let path = config_path(SEARCH_ENGINES_FILE);
+    if !path.exists() {
+        let default_engines = default_search_engines();
+        save_search_engines(&default_engines);
+        return default_engines;
+    }
+
+    load_json(&path, "Search engines").unwrap_or_else(default_search_engines)
+}
+
+/// The search engines shared by every window.
+pub fn search_engines() -> Rc<RefCell<SearchEngines>> {
+    SEARCH_ENGINES.with(Rc::clone)
+}
+
+pub fn save_search_engines(engines: &SearchEngines) {
+    save_json(&config_path(SEARCH_ENGINES_FILE), engines);
+}
+
+pub fn process_search_input(input: &str, engines: &SearchEngines) -> String {
+    let input = input.trim();
+
+    // "gh rubra tabs" searches GitHub for "rubra tabs"
+    if let Some((keyword, query)) = input.split_once(' ') {
+        let query = query.trim();
+        if let Some(engine) = engines.by_keyword(keyword) {
+            if !query.is_empty() {
+                return engine.search_url(query);
+            }
+        }
+    }
+
     if let Ok(url) = Url::parse(input) {
         if url.scheme() != "" {
             return input.to_string();
         }
 
-        if url.host_str().map_or(false, |host| host == "localhost" || host.starts_with("127.")) {
+        if url.host_str().is_some_and(|host| host == "localhost" || host.starts_with("127.")) {
             return format!("http://{}", input);
         }
 
@@ -27,6 +130,61 @@ pub fn process_search_input(input: &str) -> String {
         return format!("https://{}", input);
     }
 
-    let query = urlencoding::encode(input);
-    format!("https://duckduckgo.com/?q={}", query)
+    match engines.default_engine() {
+        Some(engine) => engine.search_url(input),
+        None => format!("https://duckduckgo.com/?q={}", urlencoding::encode(input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_pick_the_engine() {
+        let engines = default_search_engines();
+
+        assert_eq!(process_search_input("gh rubra tabs", &engines), "https://github.com/search?q=rubra%20tabs");
+        assert_eq!(
+            process_search_input("  w   Rust & C ", &engines),
+            "https://en.wikipedia.org/w/index.php?search=Rust%20%26%20C"
+        );
+        assert_eq!(
+            process_search_input("w https://example.com", &engines),
+            "https://en.wikipedia.org/w/index.php?search=https%3A%2F%2Fexample.com"
+        );
+    }
+
+    #[test]
+    fn unknown_or_lone_keywords_use_the_default_engine() {
+        let engines = default_search_engines();
+
+        assert_eq!(process_search_input("xyz tabs", &engines), "https://duckduckgo.com/?q=xyz%20tabs");
+        assert_eq!(process_search_input("gh ", &engines), "https://duckduckgo.com/?q=gh");
+    }
+
+    #[test]
+    fn detects_urls_and_domains() {
+        let engines = default_search_engines();
+
+        assert_eq!(process_search_input("https://example.com/a?b=c", &engines), "https://example.com/a?b=c");
+        assert_eq!(process_search_input("about:blank", &engines), "about:blank");
+        assert_eq!(process_search_input(" example.com ", &engines), "https://example.com");
+        assert_eq!(process_search_input("docs.example.co.uk", &engines), "https://docs.example.co.uk");
+        assert_eq!(process_search_input("example", &engines), "https://duckduckgo.com/?q=example");
+    }
+
+    #[test]
+    fn falls_back_to_the_default_engine() {
+        let mut engines = default_search_engines();
+        engines.default = "Google".to_string();
+        assert_eq!(process_search_input("rust tabs", &engines), "https://www.google.com/search?q=rust%20tabs");
+
+        engines.default = "Removed".to_string();
+        assert_eq!(process_search_input("rust", &engines), "https://duckduckgo.com/?q=rust");
+
+        engines.engines.clear();
+        assert_eq!(process_search_input("rust", &engines), "https://duckduckgo.com/?q=rust");
+        assert_eq!(engines.home_url(), "about:blank");
+    }
 }
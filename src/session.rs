@@ -0,0 +1,252 @@
+use gtk4::glib;
+use gtk4::glib::translate::{from_glib_full, ToGlibPtr};
+use gtk4::{prelude::*, Application};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use webkit6::prelude::*;
+use webkit6::{WebView, WebViewSessionState};
+
+use crate::files::write_atomic;
+use crate::tab::create_tab;
+use crate::window::{create_window, windows};
+use crate::paths::data_path;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TabState {
+    pub url: String,
+    pub title: String,
+    /// Serialized `WebViewSessionState` holding the back/forward list, base64
+    /// encoded.
+    #[serde(default)]
+    pub state: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WindowState {
+    pub tabs: Vec<TabState>,
+    pub active: usize,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl WindowState {
+    /// The tab to select, which a hand-edited file may have put past the end.
+    pub fn active_tab(&self) -> usize {
+        self.active.min(self.tabs.len().saturating_sub(1))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub windows: Vec<WindowState>,
+}
+
+const SESSION_FILE: &str = "session.json";
+const AUTOSAVE_INTERVAL_SECONDS: u32 = 15;
+
+pub fn tab_state(webview: &WebView) -> TabState {
+    let state = webview
+        .session_state()
+        .and_then(|s| s.serialize())
+        .map(|bytes| glib::base64_encode(&bytes).to_string());
+
+    TabState {
+        url: webview.uri().map(|u| u.to_string()).unwrap_or_default(),
+        title: webview.title().map(|t| t.to_string()).unwrap_or_default(),
+        state,
+    }
+}
+
+/// Restores the back/forward list of `state` into `webview` and loads its
+/// current page.
+pub fn restore_tab_state(webview: &WebView, state: &TabState) {
+    if let Some(session_state) = state.state.as_deref().and_then(decode_session_state) {
+        webview.restore_session_state(&session_state);
+
+        if let Some(item) = webview.back_forward_list().and_then(|list| list.current_item()) {
+            webview.go_to_back_forward_list_item(&item);
+            return;
+        }
+    }
+
+    webview.load_uri(&state.url);
+}
+
+// WebKit gives NULL for data it can't decode, such as state saved by another
+// WebKitGTK version, which `WebViewSessionState::new` doesn't check for
+fn decode_session_state(data: &str) -> Option<WebViewSessionState> {
+    let bytes = glib::Bytes::from_owned(glib::base64_decode(data));
+    unsafe {
+        let state = webkit6_sys::webkit_web_view_session_state_new(bytes.to_glib_none().0);
+        if state.is_null() {
+            None
+        } else {
+            Some(from_glib_full(state))
+        }
+    }
+}
+
+fn current_session() -> Session {
+    let windows = windows()
+        .iter()
+        .filter(|bw| !bw.private)
+        .map(|bw| {
+            let tabs = bw.tabs().iter().map(|tab| tab_state(&tab.webview)).collect();
+
+            WindowState {
+                tabs,
+                active: bw.notebook.current_page().unwrap_or(0) as usize,
+                width: bw.window.default_width(),
+                height: bw.window.default_height(),
+            }
+        })
+        .filter(|w| !w.tabs.is_empty())
+        .collect();
+
+    Session { windows }
+}
+
+/// Writes the open windows and tabs to the session file. The file is
+/// replaced atomically so a crash mid-write keeps the previous session.
+pub fn save_session() {
+    let session = current_session();
+    if session.windows.is_empty() {
+        return;
+    }
+
+    write_session(&data_path(SESSION_FILE), &session);
+}
+
+fn write_session(path: &Path, session: &Session) {
+    let json = serde_json::to_string_pretty(session).expect("Failed to serialize session");
+    if let Err(e) = write_atomic(path, &json) {
+        eprintln!("Unable to save session: {}", e);
+    }
+}
+
+pub fn load_session() -> Option<Session> {
+    read_session(&data_path(SESSION_FILE))
+}
+
+fn read_session(path: &Path) -> Option<Session> {
+    if !path.exists() {
+        return None;
+    }
+
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Ignoring unreadable session file: {}", e);
+            return None;
+        }
+    };
+    match serde_json::from_str(&data) {
+        Ok(session) => Some(session),
+        Err(e) => {
+            eprintln!("Ignoring unreadable session file: {}", e);
+            None
+        }
+    }
+}
+
+/// Reopens the windows of the last session, returns false if there was
+/// nothing to restore.
+pub fn restore_session(app: &Application) -> bool {
+    let Some(session) = load_session().filter(|s| s.windows.iter().any(|w| !w.tabs.is_empty())) else {
+        return false;
+    };
+
+    for window_state in session.windows.iter().filter(|w| !w.tabs.is_empty()) {
+        let browser_window = create_window(app, false);
+        browser_window.window.set_default_size(window_state.width, window_state.height);
+
+        for tab in &window_state.tabs {
+            let new_tab = create_tab("about:blank", &browser_window.notebook, app);
+            restore_tab_state(&new_tab.webview, tab);
+        }
+
+        browser_window.notebook.set_current_page(Some(window_state.active_tab() as u32));
+    }
+
+    true
+}
+
+/// Saves the session periodically so it survives crashes.
+pub fn start_session_autosave() {
+    glib::timeout_add_seconds_local(AUTOSAVE_INTERVAL_SECONDS, || {
+        save_session();
+        glib::ControlFlow::Continue
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::test_dir;
+
+    fn tab(url: &str, state: Option<&str>) -> TabState {
+        TabState {
+            url: url.to_string(),
+            title: format!("Title of {}", url),
+            state: state.map(str::to_string),
+        }
+    }
+
+    fn window(tabs: Vec<TabState>, active: usize) -> WindowState {
+        WindowState {
+            tabs,
+            active,
+            width: 1200,
+            height: 800,
+        }
+    }
+
+    #[test]
+    fn tab_states_round_trip() {
+        for state in [tab("https://a.com/", Some("AAECAw==")), tab("https://b.com/", None)] {
+            let json = serde_json::to_string(&state).unwrap();
+            let read: TabState = serde_json::from_str(&json).unwrap();
+            assert_eq!((read.url, read.title, read.state), (state.url, state.title, state.state));
+        }
+    }
+
+    #[test]
+    fn reads_tabs_saved_without_state() {
+        let read: TabState = serde_json::from_str(r#"{ "url": "https://a.com/", "title": "A" }"#).unwrap();
+        assert_eq!(read.state, None);
+    }
+
+    #[test]
+    fn session_files_round_trip() {
+        let path = test_dir("session").join(SESSION_FILE);
+        let session = Session {
+            windows: vec![
+                window(vec![tab("https://a.com/", Some("AAECAw==")), tab("https://b.com/", None)], 1),
+                window(vec![tab("https://c.com/", None)], 0),
+            ],
+        };
+
+        write_session(&path, &session);
+        let read = read_session(&path).unwrap();
+
+        assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&session).unwrap());
+    }
+
+    #[test]
+    fn ignores_missing_and_broken_session_files() {
+        let dir = test_dir("broken-session");
+        assert!(read_session(&dir.join(SESSION_FILE)).is_none());
+
+        fs::write(dir.join(SESSION_FILE), "{ \"windows\": [").unwrap();
+        assert!(read_session(&dir.join(SESSION_FILE)).is_none());
+    }
+
+    #[test]
+    fn clamps_the_active_tab() {
+        let tabs = vec![tab("https://a.com/", None), tab("https://b.com/", None)];
+        assert_eq!(window(tabs.clone(), 1).active_tab(), 1);
+        assert_eq!(window(tabs, 7).active_tab(), 1);
+        assert_eq!(window(Vec::new(), 3).active_tab(), 0);
+    }
+}
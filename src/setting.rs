@@ -1,401 +1,744 @@
-use gtk4::glib::Propagation;
+use gtk4::glib::{ControlFlow, Propagation};
 use gtk4::{
-    prelude::*, ApplicationWindow, Box, Button, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow, Stack, Switch
+    prelude::*, ApplicationWindow, Box, Button, DropDown, Entry, Label, ListBox, ListBoxRow, Orientation,
+    ScrolledWindow, SpinButton, Stack, Switch
 };
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
 use std::rc::Rc;
 use std::cell::RefCell;
+use url::Url;
 use webkit6::WebView;
 
+use crate::search::{save_search_engines, search_engines};
+use crate::paths::config_path;
+use crate::listeners::Listeners;
+use crate::files::{back_up, save_json, warn};
+
+/// A setting's value as stored in `settings.json`. Choices and URLs are
+/// stored as text.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl SettingValue {
+    pub fn as_bool(&self) -> bool {
+        matches!(self, SettingValue::Bool(true))
+    }
+
+    pub fn as_int(&self) -> i64 {
+        match self {
+            SettingValue::Int(i) => *i,
+            _ => 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SettingValue::Text(s) => s,
+            _ => "",
+        }
+    }
+}
+
+pub enum SettingKind {
+    Bool,
+    Int { min: i64, max: i64 },
+    /// One of the given values, shown with the matching labels.
+    Choice(&'static [(&'static str, &'static str)]),
+    Text,
+    /// A URL, or empty for none.
+    Url,
+}
+
+/// How a setting is applied to a web view's `webkit6::Settings`.
+pub enum Apply {
+    Bool(fn(&webkit6::Settings, bool)),
+    Int(fn(&webkit6::Settings, i64)),
+    Text(fn(&webkit6::Settings, &str)),
+    /// Used by the browser itself rather than WebKit.
+    Browser,
+}
+
+pub struct SettingSpec {
+    /// Stable key the setting is stored under.
+    pub key: &'static str,
+    pub category: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+    pub kind: SettingKind,
+    pub default: SettingValue,
+    pub apply: Apply,
+}
+
+fn bool_setting(
+    key: &'static str,
+    category: &'static str,
+    label: &'static str,
+    description: &'static str,
+    default: bool,
+    apply: Apply,
+) -> SettingSpec {
+    SettingSpec {
+        key,
+        category,
+        label,
+        description,
+        kind: SettingKind::Bool,
+        default: SettingValue::Bool(default),
+        apply,
+    }
+}
+
+const GENERAL: &str = "General Settings";
+const APPEARANCE: &str = "Appearance";
+const MEDIA: &str = "Media Settings";
+const JAVASCRIPT: &str = "JavaScript Settings";
+const WEB_FEATURES: &str = "Web Features";
+const SECURITY: &str = "Security Settings";
+const PRIVACY: &str = "Privacy";
+
+const CHARSETS: &[(&str, &str)] = &[
+    ("iso-8859-1", "Western (ISO-8859-1)"),
+    ("windows-1252", "Western (Windows-1252)"),
+    ("utf-8", "Unicode (UTF-8)"),
+    ("iso-8859-2", "Central European (ISO-8859-2)"),
+    ("windows-1251", "Cyrillic (Windows-1251)"),
+    ("koi8-r", "Cyrillic (KOI8-R)"),
+    ("shift_jis", "Japanese (Shift_JIS)"),
+    ("euc-kr", "Korean (EUC-KR)"),
+    ("gbk", "Chinese Simplified (GBK)"),
+    ("big5", "Chinese Traditional (Big5)"),
+];
+
+const COOKIE_POLICIES: &[(&str, &str)] = &[
+    ("no-third-party", "Block third-party cookies"),
+    ("always", "Accept all cookies"),
+    ("never", "Block all cookies"),
+];
+
+const HARDWARE_ACCELERATION_POLICIES: &[(&str, &str)] = &[("always", "Always"), ("never", "Never")];
+
+lazy_static! {
+    /// Every setting, in the order they are shown. Labels match the keys of
+    /// the old label-keyed settings file so it can be migrated.
+    pub static ref SCHEMA: Vec<SettingSpec> = vec![
+        bool_setting("enable-javascript", GENERAL, "Enable JavaScript",
+            "Run scripts on web pages.",
+            true, Apply::Bool(webkit6::Settings::set_enable_javascript)),
+        bool_setting("zoom-text-only", GENERAL, "Zoom Text Only",
+            "Zoom only the text of pages, not images.",
+            false, Apply::Bool(webkit6::Settings::set_zoom_text_only)),
+        bool_setting("print-backgrounds", GENERAL, "Print Backgrounds",
+            "Include background colors and images when printing.",
+            true, Apply::Bool(webkit6::Settings::set_print_backgrounds)),
+        bool_setting("auto-load-images", GENERAL, "Auto Load Images",
+            "Load images on web pages.",
+            true, Apply::Bool(webkit6::Settings::set_auto_load_images)),
+        bool_setting("load-icons-ignoring-image-load-setting", GENERAL, "Load Icons Ignoring Image Load Setting",
+            "Load site icons even when images are turned off.",
+            false, Apply::Bool(webkit6::Settings::set_load_icons_ignoring_image_load_setting)),
+        bool_setting("allow-modal-dialogs", GENERAL, "Allow Modal Dialogs",
+            "Let pages open dialogs that block the browser window.",
+            true, Apply::Bool(webkit6::Settings::set_allow_modal_dialogs)),
+        bool_setting("allow-file-access-from-file-urls", GENERAL, "Allow File Access from File URLs",
+            "Let local files read other local files.",
+            false, Apply::Bool(webkit6::Settings::set_allow_file_access_from_file_urls)),
+        bool_setting("restore-session", GENERAL, "Restore Previous Session",
+            "Reopen the windows and tabs from last time on startup.",
+            false, Apply::Browser),
+        bool_setting("ask-download-location", GENERAL, "Ask Where To Save Downloads",
+            "Choose a location for every download instead of saving it to the downloads folder.",
+            false, Apply::Browser),
+        SettingSpec {
+            key: "home-page",
+            category: GENERAL,
+            label: "Home Page",
+            description: "Page opened in new tabs and windows. Leave empty for the default search engine's home page.",
+            kind: SettingKind::Url,
+            default: SettingValue::Text(String::new()),
+            apply: Apply::Browser,
+        },
+        SettingSpec {
+            key: "user-agent",
+            category: GENERAL,
+            label: "Custom User Agent",
+            description: "Identify the browser to sites with this string. Leave empty for the default.",
+            kind: SettingKind::Text,
+            default: SettingValue::Text(String::new()),
+            apply: Apply::Text(|settings, user_agent| {
+                settings.set_user_agent(Some(user_agent).filter(|ua| !ua.is_empty()))
+            }),
+        },
+        SettingSpec {
+            key: "hardware-acceleration-policy",
+            category: GENERAL,
+            label: "Hardware Acceleration",
+            description: "Render pages with the GPU.",
+            kind: SettingKind::Choice(HARDWARE_ACCELERATION_POLICIES),
+            default: SettingValue::Text("always".to_string()),
+            apply: Apply::Text(|settings, policy| {
+                settings.set_hardware_acceleration_policy(match policy {
+                    "never" => webkit6::HardwareAccelerationPolicy::Never,
+                    _ => webkit6::HardwareAccelerationPolicy::Always,
+                })
+            }),
+        },
+
+        SettingSpec {
+            key: "default-font-family",
+            category: APPEARANCE,
+            label: "Default Font",
+            description: "Font used when a page doesn't choose one.",
+            kind: SettingKind::Text,
+            default: SettingValue::Text("sans-serif".to_string()),
+            apply: Apply::Text(webkit6::Settings::set_default_font_family),
+        },
+        SettingSpec {
+            key: "serif-font-family",
+            category: APPEARANCE,
+            label: "Serif Font",
+            description: "Font used for text in the serif family.",
+            kind: SettingKind::Text,
+            default: SettingValue::Text("serif".to_string()),
+            apply: Apply::Text(webkit6::Settings::set_serif_font_family),
+        },
+        SettingSpec {
+            key: "monospace-font-family",
+            category: APPEARANCE,
+            label: "Monospace Font",
+            description: "Font used for code and other fixed width text.",
+            kind: SettingKind::Text,
+            default: SettingValue::Text("monospace".to_string()),
+            apply: Apply::Text(webkit6::Settings::set_monospace_font_family),
+        },
+        SettingSpec {
+            key: "default-font-size",
+            category: APPEARANCE,
+            label: "Default Font Size",
+            description: "Size of text in pixels when a page doesn't choose one.",
+            kind: SettingKind::Int { min: 6, max: 72 },
+            default: SettingValue::Int(16),
+            apply: Apply::Int(|settings, size| settings.set_default_font_size(size as u32)),
+        },
+        SettingSpec {
+            key: "minimum-font-size",
+            category: APPEARANCE,
+            label: "Minimum Font Size",
+            description: "Never show text smaller than this many pixels. 0 for no minimum.",
+            kind: SettingKind::Int { min: 0, max: 72 },
+            default: SettingValue::Int(0),
+            apply: Apply::Int(|settings, size| settings.set_minimum_font_size(size as u32)),
+        },
+        SettingSpec {
+            key: "default-charset",
+            category: APPEARANCE,
+            label: "Default Text Encoding",
+            description: "Encoding used for pages that don't declare one.",
+            kind: SettingKind::Choice(CHARSETS),
+            default: SettingValue::Text("iso-8859-1".to_string()),
+            apply: Apply::Text(webkit6::Settings::set_default_charset),
+        },
+        SettingSpec {
+            key: "default-zoom",
+            category: APPEARANCE,
+            label: "Default Zoom Level",
+            description: "Zoom in percent for sites without their own zoom level.",
+            kind: SettingKind::Int { min: 30, max: 500 },
+            default: SettingValue::Int(100),
+            apply: Apply::Browser,
+        },
+
+        bool_setting("media-playback-requires-user-gesture", MEDIA, "Media Playback Requires User Gesture",
+            "Only play audio and video after interacting with the page.",
+            true, Apply::Bool(webkit6::Settings::set_media_playback_requires_user_gesture)),
+        bool_setting("media-playback-allows-inline", MEDIA, "Media Playback Allows Inline",
+            "Play videos inside the page rather than fullscreen.",
+            true, Apply::Bool(webkit6::Settings::set_media_playback_allows_inline)),
+        bool_setting("enable-media", MEDIA, "Enable Media",
+            "Support audio and video elements.",
+            true, Apply::Bool(webkit6::Settings::set_enable_media)),
+        bool_setting("enable-webaudio", MEDIA, "Enable WebAudio",
+            "Support the Web Audio API.",
+            true, Apply::Bool(webkit6::Settings::set_enable_webaudio)),
+        bool_setting("enable-webrtc", MEDIA, "Enable WebRTC",
+            "Support real time audio, video and data connections.",
+            true, Apply::Bool(webkit6::Settings::set_enable_webrtc)),
+        bool_setting("enable-media-stream", MEDIA, "Enable Media Stream",
+            "Let pages ask for the camera and microphone.",
+            true, Apply::Bool(webkit6::Settings::set_enable_media_stream)),
+        bool_setting("enable-media-capabilities", MEDIA, "Enable Media Capabilities",
+            "Let pages query which media formats can be played.",
+            true, Apply::Bool(webkit6::Settings::set_enable_media_capabilities)),
+        bool_setting("enable-encrypted-media", MEDIA, "Enable Encrypted Media",
+            "Play DRM protected audio and video.",
+            true, Apply::Bool(webkit6::Settings::set_enable_encrypted_media)),
+
+        bool_setting("javascript-can-open-windows-automatically", JAVASCRIPT, "JavaScript Can Open Windows Automatically",
+            "Let scripts open windows without a click.",
+            true, Apply::Bool(webkit6::Settings::set_javascript_can_open_windows_automatically)),
+        bool_setting("javascript-can-access-clipboard", JAVASCRIPT, "JavaScript Can Access Clipboard",
+            "Let scripts read and write the clipboard.",
+            true, Apply::Bool(webkit6::Settings::set_javascript_can_access_clipboard)),
+        bool_setting("enable-javascript-markup", JAVASCRIPT, "Enable JavaScript Markup",
+            "Run scripts embedded in the page markup.",
+            false, Apply::Bool(webkit6::Settings::set_enable_javascript_markup)),
+
+        bool_setting("enable-tabs-to-links", WEB_FEATURES, "Enable Tabs to Links",
+            "Move the focus to links with the Tab key.",
+            true, Apply::Bool(webkit6::Settings::set_enable_tabs_to_links)),
+        bool_setting("enable-spatial-navigation", WEB_FEATURES, "Enable Spatial Navigation",
+            "Move between links and form fields with the arrow keys.",
+            false, Apply::Bool(webkit6::Settings::set_enable_spatial_navigation)),
+        bool_setting("enable-caret-browsing", WEB_FEATURES, "Enable Caret Browsing",
+            "Move through pages with a text cursor.",
+            false, Apply::Bool(webkit6::Settings::set_enable_caret_browsing)),
+        bool_setting("enable-smooth-scrolling", WEB_FEATURES, "Enable Smooth Scrolling",
+            "Animate scrolling.",
+            true, Apply::Bool(webkit6::Settings::set_enable_smooth_scrolling)),
+        bool_setting("enable-resizable-text-areas", WEB_FEATURES, "Enable Resizable Text Areas",
+            "Let text areas be resized by dragging their corner.",
+            true, Apply::Bool(webkit6::Settings::set_enable_resizable_text_areas)),
+        bool_setting("enable-page-cache", WEB_FEATURES, "Enable Page Cache",
+            "Keep recent pages in memory for instant back and forward navigation.",
+            true, Apply::Bool(webkit6::Settings::set_enable_page_cache)),
+        bool_setting("enable-offline-web-application-cache", WEB_FEATURES, "Enable Offline Web Application Cache",
+            "Support the deprecated application cache.",
+            false, Apply::Bool(webkit6::Settings::set_enable_offline_web_application_cache)),
+        bool_setting("enable-html5-local-storage", WEB_FEATURES, "Enable HTML5 Local Storage",
+            "Let pages store data in local storage.",
+            true, Apply::Bool(webkit6::Settings::set_enable_html5_local_storage)),
+        bool_setting("enable-html5-database", WEB_FEATURES, "Enable HTML5 Database",
+            "Let pages store data in IndexedDB databases.",
+            false, Apply::Bool(webkit6::Settings::set_enable_html5_database)),
+        bool_setting("enable-fullscreen", WEB_FEATURES, "Enable Fullscreen",
+            "Let pages go fullscreen.",
+            true, Apply::Bool(webkit6::Settings::set_enable_fullscreen)),
+        bool_setting("enable-dns-prefetching", WEB_FEATURES, "Enable DNS Prefetching",
+            "Look up the addresses of links before they are followed.",
+            true, Apply::Bool(webkit6::Settings::set_enable_dns_prefetching)),
+        bool_setting("enable-webgl", WEB_FEATURES, "Enable WebGL",
+            "Support 3D graphics with WebGL.",
+            true, Apply::Bool(webkit6::Settings::set_enable_webgl)),
+
+        bool_setting("disable-web-security", SECURITY, "Disable Web Security",
+            "Turn off the same-origin policy. Only for testing.",
+            false, Apply::Bool(webkit6::Settings::set_disable_web_security)),
+        bool_setting("allow-universal-access-from-file-urls", SECURITY, "Allow Universal Access from File URLs",
+            "Let local files read any URL.",
+            false, Apply::Bool(webkit6::Settings::set_allow_universal_access_from_file_urls)),
+        bool_setting("allow-top-navigation-to-data-urls", SECURITY, "Allow Top Navigation to Data URLs",
+            "Let pages navigate the tab to data: URLs.",
+            false, Apply::Bool(webkit6::Settings::set_allow_top_navigation_to_data_urls)),
+        bool_setting("enable-developer-extras", SECURITY, "Enable Developer Extras",
+            "Show the web inspector in the context menu.",
+            false, Apply::Bool(webkit6::Settings::set_enable_developer_extras)),
+        bool_setting("enable-hyperlink-auditing", SECURITY, "Enable Hyperlink Auditing",
+            "Send pings for links with a ping attribute.",
+            false, Apply::Bool(webkit6::Settings::set_enable_hyperlink_auditing)),
+        bool_setting("draw-compositing-indicators", SECURITY, "Draw Compositing Indicators",
+            "Draw borders around composited layers. For debugging.",
+            false, Apply::Bool(webkit6::Settings::set_draw_compositing_indicators)),
+        bool_setting("enable-mock-capture-devices", SECURITY, "Enable Mock Capture Devices",
+            "Replace the camera and microphone with fake devices. For testing.",
+            false, Apply::Bool(webkit6::Settings::set_enable_mock_capture_devices)),
+        bool_setting("enable-site-specific-quirks", SECURITY, "Enable Site-Specific Quirks",
+            "Work around known bugs of particular sites.",
+            false, Apply::Bool(webkit6::Settings::set_enable_site_specific_quirks)),
+        bool_setting("enable-back-forward-navigation-gestures", SECURITY, "Enable Back Forward Navigation Gestures",
+            "Go back and forward with touchpad swipes.",
+            false, Apply::Bool(webkit6::Settings::set_enable_back_forward_navigation_gestures)),
+        bool_setting("enable-write-console-messages-to-stdout", SECURITY, "Enable Write Console Messages to Stdout",
+            "Print the pages' console messages to the terminal.",
+            false, Apply::Bool(webkit6::Settings::set_enable_write_console_messages_to_stdout)),
+
+        SettingSpec {
+            key: "cookie-accept-policy",
+            category: PRIVACY,
+            label: "Cookies",
+            description: "Which sites may store cookies.",
+            kind: SettingKind::Choice(COOKIE_POLICIES),
+            default: SettingValue::Text("no-third-party".to_string()),
+            apply: Apply::Browser,
+        },
+    ];
+}
+
+pub fn setting_spec(key: &str) -> Option<&'static SettingSpec> {
+    SCHEMA.iter().find(|spec| spec.key == key)
+}
+
+/// Categories in the order they first appear in the schema.
+fn categories() -> Vec<&'static str> {
+    let mut categories: Vec<&str> = Vec::new();
+    for spec in SCHEMA.iter() {
+        if !categories.contains(&spec.category) {
+            categories.push(spec.category);
+        }
+    }
+    categories
+}
+
+impl SettingSpec {
+    /// Checks that `value` has the right type and is in range for this
+    /// setting.
+    pub fn validate(&self, value: &SettingValue) -> Result<(), String> {
+        match (&self.kind, value) {
+            (SettingKind::Bool, SettingValue::Bool(_)) => Ok(()),
+            (SettingKind::Int { min, max }, SettingValue::Int(i)) if (min..=max).contains(&i) => Ok(()),
+            (SettingKind::Int { min, max }, SettingValue::Int(i)) => {
+                Err(format!("{} must be between {} and {}, not {}", self.key, min, max, i))
+            }
+            (SettingKind::Choice(choices), SettingValue::Text(s)) if choices.iter().any(|(v, _)| v == s) => Ok(()),
+            (SettingKind::Choice(_), SettingValue::Text(s)) => Err(format!("{} can't be {}", self.key, s)),
+            (SettingKind::Text, SettingValue::Text(_)) => Ok(()),
+            (SettingKind::Url, SettingValue::Text(s)) if s.is_empty() || Url::parse(s).is_ok() => Ok(()),
+            (SettingKind::Url, SettingValue::Text(s)) => Err(format!("{} is not a valid URL for {}", s, self.key)),
+            _ => Err(format!("{} has the wrong type of value", self.key)),
+        }
+    }
+
+    /// Reads a value written as text by the old settings file.
+    fn parse_legacy(&self, value: &str) -> Option<SettingValue> {
+        let value = match self.kind {
+            SettingKind::Bool => SettingValue::Bool(value == "true"),
+            SettingKind::Int { .. } => SettingValue::Int(value.parse().ok()?),
+            _ => SettingValue::Text(value.to_string()),
+        };
+        self.validate(&value).ok().map(|_| value)
+    }
+}
+
+/// Version of the settings file written by this build. Version 1 stored
+/// strings under display labels, version 2 typed values under stable keys.
+const SETTINGS_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WebkitSettings {
-    categories: Vec<CategorySettings>,
+    version: u32,
+    values: BTreeMap<String, SettingValue>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct CategorySettings {
-    name: String,
-    settings: Vec<Setting>,
+impl WebkitSettings {
+    /// Every setting at its default value.
+    pub fn defaults() -> WebkitSettings {
+        WebkitSettings {
+            version: SETTINGS_VERSION,
+            values: SCHEMA.iter().map(|spec| (spec.key.to_string(), spec.default.clone())).collect(),
+        }
+    }
+
+    /// The stored value of `key`, or its default if unset or invalid.
+    pub fn get(&self, key: &str) -> SettingValue {
+        let spec = setting_spec(key).unwrap_or_else(|| panic!("Unknown setting {}", key));
+        self.values
+            .get(key)
+            .filter(|value| spec.validate(value).is_ok())
+            .cloned()
+            .unwrap_or_else(|| spec.default.clone())
+    }
+
+    pub fn bool(&self, key: &str) -> bool {
+        self.get(key).as_bool()
+    }
+
+    pub fn int(&self, key: &str) -> i64 {
+        self.get(key).as_int()
+    }
+
+    pub fn set(&mut self, key: &str, value: SettingValue) -> Result<(), String> {
+        let spec = setting_spec(key).ok_or_else(|| format!("Unknown setting {}", key))?;
+        spec.validate(&value)?;
+        self.values.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Adds settings missing from an older file at their defaults and drops
+    /// unknown or invalid values.
+    fn merge_defaults(&mut self) {
+        self.values.retain(|key, value| match setting_spec(key) {
+            Some(spec) => match spec.validate(value) {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("Resetting invalid setting: {}", e);
+                    false
+                }
+            },
+            None => {
+                println!("Dropping unknown setting: {}", key);
+                false
+            }
+        });
+
+        for spec in SCHEMA.iter() {
+            self.values.entry(spec.key.to_string()).or_insert_with(|| spec.default.clone());
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Setting {
+// Version 1 of the settings file: values stored as "true"/"false" strings
+// under their display labels
+#[derive(Deserialize)]
+struct LegacySettings {
+    categories: Vec<LegacyCategory>,
+}
+
+#[derive(Deserialize)]
+struct LegacyCategory {
+    settings: Vec<LegacySetting>,
+}
+
+#[derive(Deserialize)]
+struct LegacySetting {
     key: String,
     value: String,
 }
 
-const SETTINGS_FILE: &str = "settings.json";
+fn migrate_legacy(legacy: LegacySettings) -> WebkitSettings {
+    let mut settings = WebkitSettings::defaults();
 
-pub fn load_settings() -> Rc<RefCell<WebkitSettings>> {
-    if Path::new(SETTINGS_FILE).exists() {
-        let data = fs::read_to_string(SETTINGS_FILE).expect("Unable to read settings file");
-        let settings: WebkitSettings = serde_json::from_str(&data).expect("Unable to parse JSON");
-        Rc::new(RefCell::new(settings))
-    } else {
-        let default_settings = WebkitSettings {
-            categories: vec![
-                CategorySettings {
-                    name: "General Settings".to_string(),
-                    settings: vec![
-                        Setting {
-                            key: "Enable JavaScript".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Zoom Text Only".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Print Backgrounds".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Auto Load Images".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Allow Modal Dialogs".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Allow File Access from File URLs".to_string(),
-                            value: "false".to_string(),
-                        },
-                    ],
-                },
-                CategorySettings {
-                    name: "Media Settings".to_string(),
-                    settings: vec![
-                        Setting {
-                            key: "Media Playback Requires User Gesture".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Media Playback Allows Inline".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Media".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable WebRTC".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Media Stream".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Media Capabilities".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Encrypted Media".to_string(),
-                            value: "true".to_string(),
-                        },
-                    ],
-                },
-                CategorySettings {
-                    name: "JavaScript Settings".to_string(),
-                    settings: vec![
-                        Setting {
-                            key: "JavaScript Can Open Windows Automatically".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "JavaScript Can Access Clipboard".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable JavaScript Markup".to_string(),
-                            value: "false".to_string(),
-                        },
-                    ],
-                },
-                CategorySettings {
-                    name: "Web Features".to_string(),
-                    settings: vec![
-                        Setting {
-                            key: "Enable Tabs to Links".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Spatial Navigation".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Smooth Scrolling".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Resizable Text Areas".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Page Cache".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Offline Web Application Cache".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable HTML5 Local Storage".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable HTML5 Database".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Fullscreen".to_string(),
-                            value: "true".to_string(),
-                        },
-                        Setting {
-                            key: "Enable DNS Prefetching".to_string(),
-                            value: "true".to_string(),
-                        },
-                    ],
-                },
-                CategorySettings {
-                    name: "Security Settings".to_string(),
-                    settings: vec![
-                        Setting {
-                            key: "Disable Web Security".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Allow Universal Access from File URLs".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Allow Top Navigation to Data URLs".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Developer Extras".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Hyperlink Auditing".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Draw Compositing Indicators".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Mock Capture Devices".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Site-Specific Quirks".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Back Forward Navigation Gestures".to_string(),
-                            value: "false".to_string(),
-                        },
-                        Setting {
-                            key: "Enable Write Console Messages to Stdout".to_string(),
-                            value: "false".to_string(),
-                        },
-                    ],
-                },
-            ],
+    for setting in legacy.categories.into_iter().flat_map(|c| c.settings) {
+        let Some(spec) = SCHEMA.iter().find(|spec| spec.label == setting.key) else {
+            println!("Dropping unknown setting: {}", setting.key);
+            continue;
         };
+        match spec.parse_legacy(&setting.value) {
+            Some(value) => {
+                settings.values.insert(spec.key.to_string(), value);
+            }
+            None => println!("Dropping invalid value {} for {}", setting.value, setting.key),
+        }
+    }
+
+    settings
+}
+
+#[derive(Debug)]
+enum SettingsError {
+    Invalid(String),
+    /// Written by a newer Rubra, which may still want the file as it is.
+    Newer(u64),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SettingsError::Invalid(e) => write!(f, "{}", e),
+            SettingsError::Newer(version) => {
+                write!(f, "Settings version {} is newer than this version of Rubra supports", version)
+            }
+        }
+    }
+}
+
+impl From<serde_json::Error> for SettingsError {
+    fn from(e: serde_json::Error) -> Self {
+        SettingsError::Invalid(e.to_string())
+    }
+}
+
+/// Reads any version of the settings file, upgrading it to the current one.
+fn parse_settings(data: &str) -> Result<WebkitSettings, SettingsError> {
+    let json: serde_json::Value = serde_json::from_str(data)?;
+
+    // Files from before the version field was added
+    let version = match json.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| SettingsError::Invalid("The settings version is not a number".to_string()))?,
+        None if json.get("categories").is_some() => 1,
+        None => 2,
+    };
+
+    let mut settings = match version {
+        1 => {
+            println!("Migrating settings file from version 1");
+            migrate_legacy(serde_json::from_value(json)?)
+        }
+        2 => WebkitSettings {
+            version: SETTINGS_VERSION,
+            values: serde_json::from_value(json["values"].clone())?,
+        },
+        _ => return Err(SettingsError::Newer(version)),
+    };
+
+    settings.merge_defaults();
+    Ok(settings)
+}
+
+/// Whether `data` is a settings file this version can read.
+pub fn is_settings_file(data: &str) -> bool {
+    parse_settings(data).is_ok()
+}
+
+const SETTINGS_FILE: &str = "settings.json";
+
+thread_local! {
+    static SETTINGS: Rc<RefCell<WebkitSettings>> = Rc::new(RefCell::new(load_settings()));
+    static LISTENERS: Listeners = Listeners::default();
+    static SETTINGS_WINDOW: RefCell<Option<ApplicationWindow>> = const { RefCell::new(None) };
+}
+
+fn load_settings() -> WebkitSettings {
+    let path = config_path(SETTINGS_FILE);
+    if !path.exists() {
+        let default_settings = WebkitSettings::defaults();
         save_settings(&default_settings);
-        Rc::new(RefCell::new(default_settings))
+        return default_settings;
     }
+
+    let loaded = fs::read_to_string(&path)
+        .map_err(|e| SettingsError::Invalid(e.to_string()))
+        .and_then(|data| parse_settings(&data).map(|settings| (settings, data)));
+
+    let error = match loaded {
+        Ok((settings, data)) => {
+            // Write back migrated or merged settings
+            if serde_json::to_string_pretty(&settings).ok().as_deref() != Some(data.as_str()) {
+                save_settings(&settings);
+            }
+            return settings;
+        }
+        Err(e) => e,
+    };
+
+    // The defaults are only used in memory when the file can't be
+    // overwritten safely, until a setting is changed
+    let detail = match (back_up(&path), &error) {
+        (Ok(backup), SettingsError::Invalid(_)) => {
+            save_settings(&WebkitSettings::defaults());
+            format!(
+                "{}\n\nAll settings were reset to their defaults. The old file was saved as {}.",
+                error,
+                backup.display()
+            )
+        }
+        (Ok(backup), SettingsError::Newer(_)) => format!(
+            "{}\n\nThe default settings are used, and the file is left as it is until you change a setting. \
+             A copy was saved as {}.",
+            error,
+            backup.display()
+        ),
+        (Err(e), _) => format!(
+            "{}\n\nThe default settings are used, and the file is left as it is until you change a setting. \
+             It couldn't be backed up: {}",
+            error, e
+        ),
+    };
+    warn("Unable to read settings", detail);
+
+    WebkitSettings::defaults()
+}
+
+/// The settings shared by every window and tab.
+pub fn settings() -> Rc<RefCell<WebkitSettings>> {
+    SETTINGS.with(Rc::clone)
+}
+
+/// Calls `f` after every change to the settings, until it returns
+/// `ControlFlow::Break`.
+pub fn connect_settings_changed<F: Fn() -> ControlFlow + 'static>(f: F) {
+    LISTENERS.with(|l| l.connect(f));
+}
+
+/// Saves the settings and notifies listeners. Call after modifying
+/// `settings()`.
+pub fn settings_changed() {
+    save_settings(&settings().borrow());
+
+    LISTENERS.with(Listeners::notify);
 }
 
+/// The page new tabs and windows open with.
+pub fn home_page() -> String {
+    match settings().borrow().get("home-page").as_str() {
+        "" => search_engines().borrow().home_url(),
+        url => url.to_string(),
+    }
+}
 
 fn save_settings(settings: &WebkitSettings) {
-    let json = serde_json::to_string_pretty(&settings).expect("Failed to serialize settings");
-    fs::write(SETTINGS_FILE, json).expect("Unable to write to settings file");
+    save_json(&config_path(SETTINGS_FILE), settings);
 }
 
 pub fn apply_settings(webview: &WebView, settings: &WebkitSettings) {
     if let Some(web_settings) = webkit6::prelude::WebViewExt::settings(webview) {
-        for category in &settings.categories {
-            for setting in &category.settings {
-                match setting.key.as_str() {
-                    "Enable JavaScript" => {
-                        let enable_js = setting.value == "true";
-                        web_settings.set_enable_javascript(enable_js);
-                    },
-                    "Zoom Text Only" => {
-                        let enable_zoom_text_only = setting.value == "true";
-                        web_settings.set_zoom_text_only(enable_zoom_text_only);
-                    },
-                    "Print Backgrounds" => {
-                        let print_backgrounds = setting.value == "true";
-                        web_settings.set_print_backgrounds(print_backgrounds);
-                    },
-                    "Media Playback Requires User Gesture" => {
-                        let requires_user_gesture = setting.value == "true";
-                        web_settings.set_media_playback_requires_user_gesture(requires_user_gesture);
-                    },
-                    "Media Playback Allows Inline" => {
-                        let allows_inline = setting.value == "true";
-                        web_settings.set_media_playback_allows_inline(allows_inline);
-                    },
-                    "Load Icons Ignoring Image Load Setting" => {
-                        let load_icons = setting.value == "true";
-                        web_settings.set_load_icons_ignoring_image_load_setting(load_icons);
-                    },
-                    "JavaScript Can Open Windows Automatically" => {
-                        let can_open_windows = setting.value == "true";
-                        web_settings.set_javascript_can_open_windows_automatically(can_open_windows);
-                    },
-                    "JavaScript Can Access Clipboard" => {
-                        let can_access_clipboard = setting.value == "true";
-                        web_settings.set_javascript_can_access_clipboard(can_access_clipboard);
-                    },
-                    "Enable Write Console Messages to Stdout" => {
-                        let enable_write_console = setting.value == "true";
-                        web_settings.set_enable_write_console_messages_to_stdout(enable_write_console);
-                    },
-                    "Enable WebRTC" => {
-                        let enable_webrtc = setting.value == "true";
-                        web_settings.set_enable_webrtc(enable_webrtc);
-                    },
-                    "Enable WebGL" => {
-                        let enable_webgl = setting.value == "true";
-                        web_settings.set_enable_webgl(enable_webgl);
-                    },
-                    "Enable WebAudio" => {
-                        let enable_webaudio = setting.value == "true";
-                        web_settings.set_enable_webaudio(enable_webaudio);
-                    },
-                    "Enable Tabs to Links" => {
-                        let enable_tabs = setting.value == "true";
-                        web_settings.set_enable_tabs_to_links(enable_tabs);
-                    },
-                    "Enable Spatial Navigation" => {
-                        let enable_spatial_navigation = setting.value == "true";
-                        web_settings.set_enable_spatial_navigation(enable_spatial_navigation);
-                    },
-                    "Enable Smooth Scrolling" => {
-                        let enable_smooth_scrolling = setting.value == "true";
-                        web_settings.set_enable_smooth_scrolling(enable_smooth_scrolling);
-                    },
-                    "Enable Site-Specific Quirks" => {
-                        let enable_site_specific_quirks = setting.value == "true";
-                        web_settings.set_enable_site_specific_quirks(enable_site_specific_quirks);
-                    },
-                    "Enable Resizable Text Areas" => {
-                        let enable_resizable_text_areas = setting.value == "true";
-                        web_settings.set_enable_resizable_text_areas(enable_resizable_text_areas);
-                    },
-                    "Enable Page Cache" => {
-                        let enable_page_cache = setting.value == "true";
-                        web_settings.set_enable_page_cache(enable_page_cache);
-                    },
-                    "Enable Offline Web Application Cache" => {
-                        let enable_offline_cache = setting.value == "true";
-                        web_settings.set_enable_offline_web_application_cache(enable_offline_cache);
-                    },
-                    "Enable Mock Capture Devices" => {
-                        let enable_mock_capture = setting.value == "true";
-                        web_settings.set_enable_mock_capture_devices(enable_mock_capture);
-                    },
-                    "Enable Media Stream" => {
-                        let enable_media_stream = setting.value == "true";
-                        web_settings.set_enable_media_stream(enable_media_stream);
-                    },
-                    "Enable Media Capabilities" => {
-                        let enable_media_capabilities = setting.value == "true";
-                        web_settings.set_enable_media_capabilities(enable_media_capabilities);
-                    },
-                    "Enable Media" => {
-                        let enable_media = setting.value == "true";
-                        web_settings.set_enable_media(enable_media);
-                    },
-                    "Enable JavaScript Markup" => {
-                        let enable_js_markup = setting.value == "true";
-                        web_settings.set_enable_javascript_markup(enable_js_markup);
-                    },
-                    "Enable Hyperlink Auditing" => {
-                        let enable_hyperlink_auditing = setting.value == "true";
-                        web_settings.set_enable_hyperlink_auditing(enable_hyperlink_auditing);
-                    },
-                    "Enable HTML5 Local Storage" => {
-                        let enable_local_storage = setting.value == "true";
-                        web_settings.set_enable_html5_local_storage(enable_local_storage);
-                    },
-                    "Enable HTML5 Database" => {
-                        let enable_database = setting.value == "true";
-                        web_settings.set_enable_html5_database(enable_database);
-                    },
-                    "Enable Fullscreen" => {
-                        let enable_fullscreen = setting.value == "true";
-                        web_settings.set_enable_fullscreen(enable_fullscreen);
-                    },
-                    "Enable Encrypted Media" => {
-                        let enable_encrypted_media = setting.value == "true";
-                        web_settings.set_enable_encrypted_media(enable_encrypted_media);
-                    },
-                    "Enable DNS Prefetching" => {
-                        let enable_dns_prefetching = setting.value == "true";
-                        web_settings.set_enable_dns_prefetching(enable_dns_prefetching);
-                    },
-                    "Enable Developer Extras" => {
-                        let enable_developer_extras = setting.value == "true";
-                        web_settings.set_enable_developer_extras(enable_developer_extras);
-                    },
-                    "Enable Caret Browsing" => {
-                        let enable_caret_browsing = setting.value == "true";
-                        web_settings.set_enable_caret_browsing(enable_caret_browsing);
-                    },
-                    "Enable Back Forward Navigation Gestures" => {
-                        let enable_back_forward_nav = setting.value == "true";
-                        web_settings.set_enable_back_forward_navigation_gestures(enable_back_forward_nav);
-                    },
-                    "Draw Compositing Indicators" => {
-                        let draw_compositing_indicators = setting.value == "true";
-                        web_settings.set_draw_compositing_indicators(draw_compositing_indicators);
-                    },
-                    "Disable Web Security" => {
-                        let disable_web_security = setting.value == "true";
-                        web_settings.set_disable_web_security(disable_web_security);
-                    },
-                    "Auto Load Images" => {
-                        let auto_load_images = setting.value == "true";
-                        web_settings.set_auto_load_images(auto_load_images);
-                    },
-                    "Allow Universal Access from File URLs" => {
-                        let allow_universal_access = setting.value == "true";
-                        web_settings.set_allow_universal_access_from_file_urls(allow_universal_access);
-                    },
-                    "Allow Top Navigation to Data URLs" => {
-                        let allow_top_navigation = setting.value == "true";
-                        web_settings.set_allow_top_navigation_to_data_urls(allow_top_navigation);
-                    },
-                    "Allow Modal Dialogs" => {
-                        let allow_modal_dialogs = setting.value == "true";
-                        web_settings.set_allow_modal_dialogs(allow_modal_dialogs);
-                    },
-                    "Allow File Access from File URLs" => {
-                        let allow_file_access = setting.value == "true";
-                        web_settings.set_allow_file_access_from_file_urls(allow_file_access);
-                    },
-                    _ => println!("Unknown setting: {}", setting.key),
-                }
+        for spec in SCHEMA.iter() {
+            let value = settings.get(spec.key);
+            match spec.apply {
+                Apply::Bool(apply) => apply(&web_settings, value.as_bool()),
+                Apply::Int(apply) => apply(&web_settings, value.as_int()),
+                Apply::Text(apply) => apply(&web_settings, value.as_str()),
+                Apply::Browser => {}
             }
         }
     }
 }
 
-pub fn create_settings_window(application: &gtk4::Application, webview: &WebView) {
+// Editor widget for one setting, calling `changed` with each new value
+fn setting_editor<F: Fn(SettingValue) + 'static>(spec: &SettingSpec, value: &SettingValue, changed: F) -> gtk4::Widget {
+    match spec.kind {
+        SettingKind::Bool => {
+            let toggle_switch = Switch::new();
+            toggle_switch.set_active(value.as_bool());
+            toggle_switch.connect_state_set(move |_, state| {
+                changed(SettingValue::Bool(state));
+                Propagation::Proceed
+            });
+            toggle_switch.upcast()
+        }
+        SettingKind::Int { min, max } => {
+            let spin = SpinButton::with_range(min as f64, max as f64, 1.0);
+            spin.set_value(value.as_int() as f64);
+            spin.connect_value_changed(move |spin| changed(SettingValue::Int(spin.value() as i64)));
+            spin.upcast()
+        }
+        SettingKind::Choice(choices) => {
+            let labels: Vec<&str> = choices.iter().map(|(_, label)| *label).collect();
+            let dropdown = DropDown::from_strings(&labels);
+            if let Some(selected) = choices.iter().position(|(v, _)| *v == value.as_str()) {
+                dropdown.set_selected(selected as u32);
+            }
+            dropdown.connect_selected_notify(move |dropdown| {
+                if let Some((value, _)) = choices.get(dropdown.selected() as usize) {
+                    changed(SettingValue::Text(value.to_string()));
+                }
+            });
+            dropdown.upcast()
+        }
+        SettingKind::Text | SettingKind::Url => {
+            let entry = Entry::new();
+            entry.set_text(value.as_str());
+            entry.set_hexpand(true);
+            // Saved on Enter, so half typed URLs aren't validated
+            entry.connect_activate(move |entry| changed(SettingValue::Text(entry.text().to_string())));
+            entry.upcast()
+        }
+    }
+}
+
+/// Opens the settings window, or brings it to the front if it is already
+/// open.
+pub fn create_settings_window(application: &gtk4::Application) {
+    if let Some(window) = SETTINGS_WINDOW.with(|w| w.borrow().clone()) {
+        window.present();
+        return;
+    }
+
     let window = ApplicationWindow::new(application);
     window.set_title(Some("aapelix/rubra/settings"));
     window.set_default_size(900, 600);
 
-    let settings = load_settings();
+    window.connect_destroy(|_| {
+        SETTINGS_WINDOW.with(|w| w.borrow_mut().take());
+    });
+    SETTINGS_WINDOW.with(|w| *w.borrow_mut() = Some(window.clone()));
 
     let vbox = Box::new(Orientation::Horizontal, 10);
     let stack = Stack::new();
@@ -406,65 +749,181 @@ pub fn create_settings_window(application: &gtk4::Application, webview: &WebView
     scrolled_window.set_vexpand(true);
     scrolled_window.set_hexpand(true);
 
-    for category in settings.borrow_mut().categories.iter_mut() {
+    for category in categories() {
         // Create a button for each category in the sidebar
-        let button = Button::with_label(&category.name);
-        let category_clone = category.name.clone();
+        let button = Button::with_label(category);
         let stack_clone = stack.clone();
 
         button.connect_clicked(move |_| {
-            stack_clone.set_visible_child_name(&category_clone);
+            stack_clone.set_visible_child_name(category);
         });
 
         sidebar.append(&button);
     }
 
     // Create a scrolled window for the settings
-    for category in settings.borrow_mut().categories.iter_mut() {
+    for category in categories() {
         let category_box = ListBox::new();
-        for setting in category.settings.iter_mut() {
+        for spec in SCHEMA.iter().filter(|spec| spec.category == category) {
             let row = ListBoxRow::new();
 
             let hbox = Box::new(Orientation::Horizontal, 10);
-            let label = Label::new(Some(&setting.key));
-            hbox.append(&label);
-
-            let toggle_switch = Switch::new();
-            toggle_switch.set_active(setting.value == "true");
-            hbox.append(&toggle_switch);
+            let labels = Box::new(Orientation::Vertical, 2);
+            labels.set_hexpand(true);
 
-            let setting_key = setting.key.clone();
-            let settings_clone = Rc::clone(&settings);
-            let webview_clone = webview.clone();
+            let label = Label::new(Some(spec.label));
+            label.set_xalign(0.0);
+            labels.append(&label);
 
-            toggle_switch.connect_state_set(move |_, state| {
-                let new_value = if state { "true".to_string() } else { "false".to_string() };
-                println!("Setting '{}' changed to {}", setting_key, new_value);
+            let description = Label::new(Some(spec.description));
+            description.set_xalign(0.0);
+            description.add_css_class("dim-label");
+            labels.append(&description);
+            hbox.append(&labels);
 
-                settings_clone.borrow_mut().categories.iter_mut()
-                    .flat_map(|c| c.settings.iter_mut())
-                    .find(|s| s.key == setting_key)
-                    .map(|s| s.value = new_value);
+            let key = spec.key;
 
-                save_settings(&settings_clone.borrow());
+            let editor = setting_editor(spec, &settings().borrow().get(key), move |value| {
+                println!("Setting '{}' changed to {:?}", key, value);
 
-                apply_settings(&webview_clone, &settings_clone.borrow());
+                if let Err(e) = settings().borrow_mut().set(key, value) {
+                    println!("{}", e);
+                    return;
+                }
 
-                Propagation::Stop
+                settings_changed();
             });
+            editor.set_valign(gtk4::Align::Center);
+            hbox.append(&editor);
 
             row.set_child(Some(&hbox));
             category_box.append(&row);
         }
-        stack.add_titled(&category_box, Some(&category.name), &category.name);
+        stack.add_titled(&category_box, Some(category), category);
+    }
+
+    // Search engine selection lives in its own file, but is edited here as well
+    let engines = search_engines();
+    let engines_name = "Search Engines";
+
+    let button = Button::with_label(engines_name);
+    let stack_clone = stack.clone();
+    button.connect_clicked(move |_| {
+        stack_clone.set_visible_child_name(engines_name);
+    });
+    sidebar.append(&button);
+
+    let engines_box = ListBox::new();
+
+    let names: Vec<String> = engines.borrow().engines.iter().map(|e| e.name.clone()).collect();
+    let name_refs: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+
+    let default_row = ListBoxRow::new();
+    let hbox = Box::new(Orientation::Horizontal, 10);
+    hbox.append(&Label::new(Some("Default Search Engine")));
+
+    let dropdown = DropDown::from_strings(&name_refs);
+    if let Some(selected) = names.iter().position(|n| *n == engines.borrow().default) {
+        dropdown.set_selected(selected as u32);
+    }
+    hbox.append(&dropdown);
+
+    let engines_clone = Rc::clone(&engines);
+    dropdown.connect_selected_notify(move |dropdown| {
+        if let Some(name) = names.get(dropdown.selected() as usize) {
+            println!("Default search engine changed to {}", name);
+            engines_clone.borrow_mut().default = name.clone();
+            save_search_engines(&engines_clone.borrow());
+        }
+    });
+
+    default_row.set_child(Some(&hbox));
+    engines_box.append(&default_row);
+
+    for engine in engines.borrow().engines.iter() {
+        let row = ListBoxRow::new();
+        let hbox = Box::new(Orientation::Horizontal, 10);
+        hbox.append(&Label::new(Some(&engine.name)));
+        hbox.append(&Label::new(Some(&engine.keyword)));
+        hbox.append(&Label::new(Some(&engine.url)));
+        row.set_child(Some(&hbox));
+        engines_box.append(&row);
     }
 
+    stack.add_titled(&engines_box, Some(engines_name), engines_name);
+
     vbox.append(&sidebar); // Add sidebar to the main vertical box
     vbox.append(&scrolled_window); // Add scrolled window for the settings
     scrolled_window.set_child(Some(&stack)); // Set the stack in the scrolled window
 
     window.set_child(Some(&vbox)); // Set the main vertical box in the window
-    window.show();
+    window.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_label_keyed_files() {
+        let data = r#"{
+            "categories": [
+                {
+                    "name": "General Settings",
+                    "settings": [
+                        { "key": "Enable JavaScript", "value": "false" },
+                        { "key": "Zoom Text Only", "value": "true" },
+                        { "key": "No Longer A Setting", "value": "true" }
+                    ]
+                }
+            ]
+        }"#;
 
-    apply_settings(webview, &settings.borrow());
+        let settings = parse_settings(data).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert!(!settings.bool("enable-javascript"));
+        assert!(settings.bool("zoom-text-only"));
+        assert_eq!(settings.int("default-font-size"), 16);
+        assert_eq!(settings.values.len(), SCHEMA.len());
+    }
+
+    #[test]
+    fn reads_current_files() {
+        let mut expected = WebkitSettings::defaults();
+        expected.set("enable-javascript", SettingValue::Bool(false)).unwrap();
+        expected.set("default-font-size", SettingValue::Int(20)).unwrap();
+        expected.set("user-agent", SettingValue::Text("Test".to_string())).unwrap();
+        let data = serde_json::to_string_pretty(&expected).unwrap();
+
+        let settings = parse_settings(&data).unwrap();
+        assert_eq!(settings.values, expected.values);
+    }
+
+    #[test]
+    fn merges_missing_and_invalid_keys() {
+        let data = r#"{
+            "version": 2,
+            "values": {
+                "enable-javascript": false,
+                "default-font-size": 1000,
+                "no-longer-a-setting": true
+            }
+        }"#;
+
+        let settings = parse_settings(data).unwrap();
+        assert!(!settings.bool("enable-javascript"));
+        assert_eq!(settings.int("default-font-size"), 16);
+        assert_eq!(settings.get("cookie-accept-policy").as_str(), "no-third-party");
+        assert!(!settings.values.contains_key("no-longer-a-setting"));
+        assert_eq!(settings.values.len(), SCHEMA.len());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_settings("").is_err());
+        assert!(parse_settings("not json").is_err());
+        assert!(parse_settings(r#"{ "version": "two" }"#).is_err());
+        assert!(parse_settings(r#"{ "version": 2, "values": [1, 2] }"#).is_err());
+        assert!(matches!(parse_settings(r#"{ "version": 99, "values": {} }"#), Err(SettingsError::Newer(99))));
+    }
 }
@@ -0,0 +1,198 @@
+use gtk4::{
+    gio, glib, prelude::*, AlertDialog, Application, ApplicationWindow, Box, Button, Entry, Label, ListBox,
+    ListBoxRow, Orientation, ScrolledWindow,
+};
+use gtk4::glib::thread_guard::ThreadGuard;
+use std::rc::Rc;
+use webkit6::{WebsiteData, WebsiteDataManager, WebsiteDataTypes};
+
+use crate::downloads::format_size;
+use crate::network::network_session;
+
+// Stored data shown in the manager, with the names it is listed under
+const DATA_TYPES: [(WebsiteDataTypes, &str); 7] = [
+    (WebsiteDataTypes::COOKIES, "Cookies"),
+    (WebsiteDataTypes::LOCAL_STORAGE, "Local storage"),
+    (WebsiteDataTypes::INDEXEDDB_DATABASES, "IndexedDB"),
+    (WebsiteDataTypes::DISK_CACHE, "Cache"),
+    (WebsiteDataTypes::DOM_CACHE, "Cache storage"),
+    (WebsiteDataTypes::SERVICE_WORKER_REGISTRATIONS, "Service workers"),
+    (WebsiteDataTypes::HSTS_CACHE, "HSTS policies"),
+];
+
+fn data_types() -> WebsiteDataTypes {
+    DATA_TYPES.iter().fold(WebsiteDataTypes::empty(), |types, (t, _)| types | *t)
+}
+
+fn describe_types(types: WebsiteDataTypes) -> String {
+    DATA_TYPES
+        .iter()
+        .filter(|(t, _)| types.contains(*t))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn data_manager() -> Option<WebsiteDataManager> {
+    network_session().website_data_manager()
+}
+
+fn show_error(window: &ApplicationWindow, message: &str, error: glib::Error) {
+    AlertDialog::builder()
+        .message(message)
+        .detail(error.to_string())
+        .build()
+        .show(Some(window));
+}
+
+struct SiteDataView {
+    window: ApplicationWindow,
+    list: ListBox,
+    filter: Entry,
+    total: Label,
+}
+
+impl SiteDataView {
+    /// Fetches the stored data again and rebuilds the list.
+    fn refresh(self: &Rc<Self>) {
+        let Some(manager) = data_manager() else {
+            return;
+        };
+
+        let view = Rc::clone(self);
+        manager.fetch(data_types(), None::<&gio::Cancellable>, move |result| match result {
+            Ok(sites) => view.show(sites),
+            Err(e) => show_error(&view.window, "Unable to read site data", e),
+        });
+    }
+
+    fn show(self: &Rc<Self>, mut sites: Vec<WebsiteData>) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        let filter = self.filter.text().to_lowercase();
+        sites.retain(|site| site.name().is_some_and(|name| name.to_lowercase().contains(&filter)));
+        sites.sort_by_key(|site| site.name().map(|name| name.to_string()).unwrap_or_default());
+
+        let total: u64 = sites.iter().map(|site| site.size(data_types())).sum();
+        self.total.set_text(&format!("{} sites, {}", sites.len(), format_size(total)));
+
+        for site in sites {
+            let row = ListBoxRow::new();
+            let hbox = Box::new(Orientation::Horizontal, 10);
+
+            let labels = Box::new(Orientation::Vertical, 2);
+            labels.set_hexpand(true);
+
+            let name = Label::new(site.name().as_deref());
+            name.set_xalign(0.0);
+            labels.append(&name);
+
+            let types = Label::new(Some(&describe_types(site.types())));
+            types.set_xalign(0.0);
+            types.add_css_class("dim-label");
+            labels.append(&types);
+            hbox.append(&labels);
+
+            // Cookies don't report a size
+            let size = site.size(data_types());
+            if size > 0 {
+                hbox.append(&Label::new(Some(&format_size(size))));
+            }
+
+            let remove = Button::with_label("Remove");
+            let view = Rc::clone(self);
+            remove.connect_clicked(move |_| {
+                let Some(manager) = data_manager() else {
+                    return;
+                };
+                let view = ThreadGuard::new(Rc::clone(&view));
+                manager.remove(data_types(), &[&site], None::<&gio::Cancellable>, move |result| {
+                    let view = view.into_inner();
+                    if let Err(e) = result {
+                        show_error(&view.window, "Unable to remove site data", e);
+                    }
+                    view.refresh();
+                });
+            });
+            hbox.append(&remove);
+
+            row.set_child(Some(&hbox));
+            self.list.append(&row);
+        }
+    }
+
+    fn remove_all(self: &Rc<Self>) {
+        let dialog = AlertDialog::builder()
+            .message("Remove all site data?")
+            .detail("You will be signed out of all sites.")
+            .buttons(["Cancel", "Remove"])
+            .cancel_button(0)
+            .default_button(0)
+            .build();
+
+        let view = Rc::clone(self);
+        dialog.choose(Some(&self.window), None::<&gio::Cancellable>, move |result| {
+            if !matches!(result, Ok(1)) {
+                return;
+            }
+            let Some(manager) = data_manager() else {
+                return;
+            };
+            let view = ThreadGuard::new(view);
+            manager.clear(data_types(), glib::TimeSpan(0), None::<&gio::Cancellable>, move |result| {
+                let view = view.into_inner();
+                if let Err(e) = result {
+                    show_error(&view.window, "Unable to remove site data", e);
+                }
+                view.refresh();
+            });
+        });
+    }
+}
+
+/// Window listing the sites with stored cookies and other data.
+pub fn create_site_data_window(application: &Application) {
+    let window = ApplicationWindow::new(application);
+    window.set_title(Some("aapelix/rubra/site-data"));
+    window.set_default_size(800, 600);
+
+    let content = Box::new(Orientation::Vertical, 10);
+    let toolbar = Box::new(Orientation::Horizontal, 10);
+
+    let filter = Entry::new();
+    filter.set_placeholder_text(Some("Search sites"));
+    filter.set_hexpand(true);
+    toolbar.append(&filter);
+
+    let total = Label::new(None);
+    toolbar.append(&total);
+
+    let remove_all = Button::with_label("Remove All");
+    toolbar.append(&remove_all);
+
+    let list = ListBox::new();
+    let scrolled_window = ScrolledWindow::new();
+    scrolled_window.set_vexpand(true);
+    scrolled_window.set_child(Some(&list));
+
+    content.append(&toolbar);
+    content.append(&scrolled_window);
+    window.set_child(Some(&content));
+
+    let view = Rc::new(SiteDataView {
+        window: window.clone(),
+        list,
+        filter: filter.clone(),
+        total,
+    });
+    view.refresh();
+
+    let view_clone = Rc::clone(&view);
+    filter.connect_changed(move |_| view_clone.refresh());
+
+    remove_all.connect_clicked(move |_| view.remove_all());
+
+    window.present();
+}
@@ -0,0 +1,482 @@
+use gtk4::glib::ControlFlow;
+use gtk4::{
+    prelude::*, Box, Button, DropDown, Entry, EntryIconPosition, Grid, Label, Orientation, Popover, PositionType,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use url::Url;
+use webkit6::prelude::*;
+use webkit6::{LoadEvent, WebView};
+
+use crate::files::{load_json, save_json};
+use crate::setting::{apply_settings, connect_settings_changed, settings};
+use crate::paths::config_path;
+use crate::tab::tabs;
+use crate::window::is_private;
+
+const SITE_SETTINGS_FILE: &str = "site_settings.json";
+/// Zoom levels stepped through by zooming in and out.
+const ZOOM_LEVELS: [f64; 15] = [0.3, 0.5, 0.67, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0, 4.0, 5.0];
+
+/// Overrides of the global settings for one host. `None` uses the global
+/// setting.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SiteSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub javascript: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<bool>,
+    /// Media may play without the user interacting with the page first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoplay: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f64>,
+}
+
+pub struct SiteSettingsStore {
+    sites: BTreeMap<String, SiteSettings>,
+    path: PathBuf,
+    // Overrides changed in private windows, kept in memory until they close
+    private: BTreeMap<String, SiteSettings>,
+}
+
+thread_local! {
+    static SITE_SETTINGS: Rc<RefCell<SiteSettingsStore>> = Rc::new(RefCell::new(SiteSettingsStore::load(config_path(SITE_SETTINGS_FILE))));
+}
+
+/// The per-site overrides shared by every tab.
+pub fn site_settings() -> Rc<RefCell<SiteSettingsStore>> {
+    SITE_SETTINGS.with(Rc::clone)
+}
+
+impl SiteSettingsStore {
+    fn load(path: PathBuf) -> SiteSettingsStore {
+        SiteSettingsStore {
+            sites: load_json(&path, "Site settings").unwrap_or_default(),
+            path,
+            private: BTreeMap::new(),
+        }
+    }
+
+    fn save(&self) {
+        save_json(&self.path, &self.sites);
+    }
+
+    pub fn get(&self, host: &str) -> SiteSettings {
+        self.sites.get(host).cloned().unwrap_or_default()
+    }
+
+    /// Stores the overrides for `host`, dropping the host once nothing is
+    /// overridden.
+    pub fn set(&mut self, host: &str, settings: SiteSettings) {
+        if settings == SiteSettings::default() {
+            self.sites.remove(host);
+        } else {
+            self.sites.insert(host.to_string(), settings);
+        }
+        self.save();
+    }
+
+    /// The overrides for `host` in private windows: the ones changed there,
+    /// otherwise the saved ones.
+    pub fn get_private(&self, host: &str) -> SiteSettings {
+        self.private.get(host).cloned().unwrap_or_else(|| self.get(host))
+    }
+
+    /// Overrides `host` in private windows without saving anything.
+    pub fn set_private(&mut self, host: &str, settings: SiteSettings) {
+        self.private.insert(host.to_string(), settings);
+    }
+
+    pub fn forget_private(&mut self) {
+        self.private.clear();
+    }
+}
+
+/// The overrides for `host` as seen by `webview`.
+fn webview_site_settings(webview: &WebView, host: &str) -> SiteSettings {
+    let store = site_settings();
+    let store = store.borrow();
+    if is_private(webview) {
+        store.get_private(host)
+    } else {
+        store.get(host)
+    }
+}
+
+// Changes made in private tabs only last until the private windows close
+fn set_webview_site_settings(webview: &WebView, host: &str, settings: SiteSettings) {
+    let store = site_settings();
+    let mut store = store.borrow_mut();
+    if is_private(webview) {
+        store.set_private(host, settings);
+    } else {
+        store.set(host, settings);
+    }
+}
+
+/// Host the site settings of `uri` are stored under.
+pub fn site_host(uri: &str) -> Option<String> {
+    let url = Url::parse(uri).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let host = url.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+/// Applies the global settings to `webview`, then the overrides of the site
+/// it is showing.
+pub fn apply_site_settings(webview: &WebView) {
+    let settings = settings();
+    apply_settings(webview, &settings.borrow());
+
+    let site = webview
+        .uri()
+        .and_then(|uri| site_host(&uri))
+        .map(|host| webview_site_settings(webview, &host))
+        .unwrap_or_default();
+
+    if let Some(web_settings) = WebViewExt::settings(webview) {
+        if let Some(javascript) = site.javascript {
+            web_settings.set_enable_javascript(javascript);
+        }
+        if let Some(images) = site.images {
+            web_settings.set_auto_load_images(images);
+        }
+        if let Some(autoplay) = site.autoplay {
+            web_settings.set_media_playback_requires_user_gesture(!autoplay);
+        }
+    }
+
+    webview.set_zoom_level(site.zoom.unwrap_or_else(default_zoom));
+}
+
+fn default_zoom() -> f64 {
+    settings().borrow().int("default-zoom") as f64 / 100.0
+}
+
+/// The next of the zoom levels above `zoom`, or below it when zooming out.
+pub fn next_zoom_level(zoom: f64, zoom_in: bool) -> f64 {
+    if zoom_in {
+        ZOOM_LEVELS.iter().copied().find(|z| *z > zoom + 0.001).unwrap_or(ZOOM_LEVELS[ZOOM_LEVELS.len() - 1])
+    } else {
+        ZOOM_LEVELS.iter().rev().copied().find(|z| *z < zoom - 0.001).unwrap_or(ZOOM_LEVELS[0])
+    }
+}
+
+/// Zooms `webview` and every other tab of the same kind showing the same
+/// site, remembering the zoom for the site. `None` goes back to the default
+/// zoom. Private tabs only remember it until the private windows close.
+pub fn set_site_zoom(webview: &WebView, zoom: Option<f64>) {
+    let default = default_zoom();
+    let zoom = zoom.filter(|z| (z - default).abs() > 0.001);
+
+    let Some(host) = webview.uri().and_then(|uri| site_host(&uri)) else {
+        webview.set_zoom_level(zoom.unwrap_or(default));
+        return;
+    };
+
+    let site = SiteSettings {
+        zoom,
+        ..webview_site_settings(webview, &host)
+    };
+    set_webview_site_settings(webview, &host, site);
+
+    let private = is_private(webview);
+    for tab in tabs() {
+        let same_site = tab.webview.uri().and_then(|uri| site_host(&uri)).as_deref() == Some(host.as_str());
+        if same_site && is_private(&tab.webview) == private {
+            tab.webview.set_zoom_level(zoom.unwrap_or(default));
+        }
+    }
+}
+
+/// Toolbar button showing the zoom of `webview` while it isn't the default,
+/// resetting it when clicked.
+pub fn create_zoom_button(webview: &WebView) -> Button {
+    let button = Button::new();
+    button.add_css_class("flat");
+    button.set_tooltip_text(Some("Reset zoom"));
+
+    let update = {
+        let button = button.downgrade();
+        move |webview: &WebView| {
+            let Some(button) = button.upgrade() else {
+                return;
+            };
+            let zoom = webview.zoom_level();
+            button.set_label(&format!("{:.0}%", zoom * 100.0));
+            button.set_visible((zoom - default_zoom()).abs() > 0.001);
+        }
+    };
+    update(webview);
+    webview.connect_zoom_level_notify(move |webview| update(webview));
+
+    let webview = webview.downgrade();
+    button.connect_clicked(move |_| {
+        if let Some(webview) = webview.upgrade() {
+            set_site_zoom(&webview, None);
+        }
+    });
+
+    button
+}
+
+/// Re-applies the site settings whenever `webview` starts loading a page, so
+/// they are in place before the new document is created, and whenever the
+/// global settings change.
+pub fn connect_site_settings(webview: &WebView) {
+    webview.connect_load_changed(|webview, event| {
+        if matches!(event, LoadEvent::Started | LoadEvent::Redirected) {
+            apply_site_settings(webview);
+        }
+    });
+
+    let webview = webview.downgrade();
+    connect_settings_changed(move || {
+        let Some(webview) = webview.upgrade() else {
+            return ControlFlow::Break;
+        };
+        apply_site_settings(&webview);
+        ControlFlow::Continue
+    });
+}
+
+// Default / Allow / Block choice for a boolean override
+fn override_dropdown(value: Option<bool>) -> DropDown {
+    let dropdown = DropDown::from_strings(&["Default", "Allow", "Block"]);
+    dropdown.set_selected(match value {
+        None => 0,
+        Some(true) => 1,
+        Some(false) => 2,
+    });
+    dropdown
+}
+
+fn selected_override(dropdown: &DropDown) -> Option<bool> {
+    match dropdown.selected() {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
+
+fn zoom_dropdown(value: Option<f64>) -> DropDown {
+    let mut labels = vec!["Default".to_string()];
+    labels.extend(ZOOM_LEVELS.iter().map(|z| format!("{:.0}%", z * 100.0)));
+    let labels: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
+
+    let dropdown = DropDown::from_strings(&labels);
+    let selected = value
+        .and_then(|zoom| ZOOM_LEVELS.iter().position(|z| (z - zoom).abs() < 0.001))
+        .map_or(0, |i| i as u32 + 1);
+    dropdown.set_selected(selected);
+    dropdown
+}
+
+fn selected_zoom(dropdown: &DropDown) -> Option<f64> {
+    match dropdown.selected() {
+        0 => None,
+        i => ZOOM_LEVELS.get(i as usize - 1).copied(),
+    }
+}
+
+fn fill_site_info(popover: &Popover, webview: &WebView) {
+    let vbox = Box::new(Orientation::Vertical, 10);
+    vbox.set_margin_top(5);
+    vbox.set_margin_bottom(5);
+    vbox.set_margin_start(5);
+    vbox.set_margin_end(5);
+
+    let uri = webview.uri().map(|u| u.to_string()).unwrap_or_default();
+    let Some(host) = site_host(&uri) else {
+        vbox.append(&Label::new(Some("This page has no site settings")));
+        popover.set_child(Some(&vbox));
+        return;
+    };
+
+    let title = Label::new(Some(&host));
+    title.add_css_class("title-4");
+    vbox.append(&title);
+
+    let secure = uri.starts_with("https://");
+    let connection = Label::new(Some(if secure { "Connection is secure" } else { "Connection is not secure" }));
+    connection.add_css_class("dim-label");
+    vbox.append(&connection);
+
+    let site = webview_site_settings(webview, &host);
+
+    let javascript = override_dropdown(site.javascript);
+    let images = override_dropdown(site.images);
+    let autoplay = override_dropdown(site.autoplay);
+    let zoom = zoom_dropdown(site.zoom);
+
+    let grid = Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    let rows: [(&str, &DropDown); 4] =
+        [("JavaScript", &javascript), ("Images", &images), ("Autoplay", &autoplay), ("Zoom", &zoom)];
+    for (row, (label, dropdown)) in rows.into_iter().enumerate() {
+        let label = Label::new(Some(label));
+        label.set_xalign(0.0);
+        label.set_hexpand(true);
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(dropdown, 1, row as i32, 1, 1);
+    }
+    vbox.append(&grid);
+
+    let reset = Button::with_label("Reset site settings");
+    vbox.append(&reset);
+
+    let save = {
+        let host = host.clone();
+        let webview = webview.clone();
+        // Weak, as the dropdowns' own handlers hold this closure
+        let (javascript, images, autoplay, zoom) =
+            (javascript.downgrade(), images.downgrade(), autoplay.downgrade(), zoom.downgrade());
+        move || {
+            let (Some(javascript), Some(images), Some(autoplay), Some(zoom)) =
+                (javascript.upgrade(), images.upgrade(), autoplay.upgrade(), zoom.upgrade())
+            else {
+                return;
+            };
+            let settings = SiteSettings {
+                javascript: selected_override(&javascript),
+                images: selected_override(&images),
+                autoplay: selected_override(&autoplay),
+                zoom: selected_zoom(&zoom),
+            };
+            let previous = webview_site_settings(&webview, &host);
+            let reload = previous.javascript != settings.javascript
+                || previous.images != settings.images
+                || previous.autoplay != settings.autoplay;
+            set_webview_site_settings(&webview, &host, settings);
+
+            apply_site_settings(&webview);
+            // Script and image settings only take effect on a new document
+            if reload {
+                webview.reload();
+            }
+        }
+    };
+    let save = Rc::new(save);
+
+    for dropdown in [&javascript, &images, &autoplay, &zoom] {
+        let save = Rc::clone(&save);
+        dropdown.connect_selected_notify(move |_| save());
+    }
+
+    let popover_clone = popover.clone();
+    let webview = webview.clone();
+    reset.connect_clicked(move |_| {
+        set_webview_site_settings(&webview, &host, SiteSettings::default());
+        apply_site_settings(&webview);
+        webview.reload();
+        popover_clone.popdown();
+    });
+
+    popover.set_child(Some(&vbox));
+}
+
+/// Adds a site info icon to the address bar, opening a popover to edit the
+/// settings of the site shown in `webview`.
+pub fn attach_site_info(entry: &Entry, webview: &WebView) {
+    entry.set_icon_from_icon_name(EntryIconPosition::Primary, Some("dialog-information-symbolic"));
+    entry.set_icon_tooltip_text(EntryIconPosition::Primary, Some("Site settings"));
+
+    let popover = Popover::new();
+    popover.set_parent(entry);
+    popover.set_position(PositionType::Bottom);
+
+    let webview = webview.clone();
+    let popover_clone = popover.clone();
+    entry.connect_icon_press(move |entry, position| {
+        if position != EntryIconPosition::Primary {
+            return;
+        }
+        fill_site_info(&popover_clone, &webview);
+        popover_clone.set_pointing_to(Some(&entry.icon_area(position)));
+        popover_clone.popup();
+    });
+
+    entry.connect_destroy(move |_| {
+        popover.unparent();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::test_dir;
+
+    #[test]
+    fn normalizes_site_hosts() {
+        assert_eq!(site_host("https://www.example.com/page"), Some("example.com".to_string()));
+        assert_eq!(site_host("HTTP://Example.COM:8080/"), Some("example.com".to_string()));
+        assert_eq!(site_host("http://192.168.1.1:8000/"), Some("192.168.1.1".to_string()));
+        assert_eq!(site_host("http://[::1]/"), Some("[::1]".to_string()));
+        assert_eq!(site_host("file:///home/user/page.html"), None);
+        assert_eq!(site_host("about:blank"), None);
+        assert_eq!(site_host("rubra://history"), None);
+        assert_eq!(site_host("not a url"), None);
+    }
+
+    #[test]
+    fn stores_only_overridden_sites() {
+        let path = test_dir("site-settings").join(SITE_SETTINGS_FILE);
+        let mut store = SiteSettingsStore::load(path.clone());
+        assert_eq!(store.get("example.com"), SiteSettings::default());
+
+        let settings = SiteSettings {
+            javascript: Some(false),
+            zoom: Some(1.5),
+            ..SiteSettings::default()
+        };
+        store.set("example.com", settings.clone());
+        store.set("example.org", SiteSettings { images: Some(false), ..SiteSettings::default() });
+        assert_eq!(SiteSettingsStore::load(path.clone()).get("example.com"), settings);
+
+        store.set("example.org", SiteSettings::default());
+        let reloaded = SiteSettingsStore::load(path);
+        assert_eq!(reloaded.sites.keys().collect::<Vec<_>>(), ["example.com"]);
+    }
+
+    #[test]
+    fn steps_through_zoom_levels() {
+        assert_eq!(next_zoom_level(1.0, true), 1.1);
+        assert_eq!(next_zoom_level(1.0, false), 0.9);
+        // Zoom levels set elsewhere snap to the next one in the list
+        assert_eq!(next_zoom_level(1.2, true), 1.25);
+        assert_eq!(next_zoom_level(1.2, false), 1.1);
+    }
+
+    #[test]
+    fn stops_at_the_ends_of_the_zoom_levels() {
+        assert_eq!(next_zoom_level(5.0, true), 5.0);
+        assert_eq!(next_zoom_level(0.3, false), 0.3);
+        assert_eq!(next_zoom_level(8.0, true), 5.0);
+        assert_eq!(next_zoom_level(0.1, false), 0.3);
+        assert_eq!(next_zoom_level(0.1, true), 0.3);
+        assert_eq!(next_zoom_level(8.0, false), 5.0);
+    }
+
+    #[test]
+    fn keeps_private_overrides_in_memory() {
+        let path = test_dir("private-site-settings").join(SITE_SETTINGS_FILE);
+        let mut store = SiteSettingsStore::load(path.clone());
+        store.set("example.com", SiteSettings { images: Some(false), ..SiteSettings::default() });
+
+        let private = SiteSettings { javascript: Some(false), ..SiteSettings::default() };
+        store.set_private("example.com", private.clone());
+        assert_eq!(store.get_private("example.com"), private);
+        assert_eq!(store.get("example.com").javascript, None);
+        assert_eq!(SiteSettingsStore::load(path).get("example.com").javascript, None);
+
+        store.forget_private();
+        assert_eq!(store.get_private("example.com").images, Some(false));
+    }
+}
@@ -1,14 +1,159 @@
-use gtk4::{Application, Button, Entry};
+use gtk4::{Application, Button, Entry, Image, MenuButton, Popover, Spinner, Widget};
 use gtk4::{prelude::*, Box, Label, Notebook};
-use webkit6::{CookieManager, NetworkSession, WebView};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use webkit6::{
+    LoadEvent, NavigationPolicyDecision, NavigationType, PolicyDecisionType, ResponsePolicyDecision, WebView,
+};
 use webkit6::prelude::*;
 
-use crate::search::process_search_input;
-use crate::setting::{create_settings_window, load_settings, apply_settings};
+use crate::adblock::{connect_content_blocker, create_blocker_button};
+use crate::bookmarks::{create_bookmarks_bar, create_bookmarks_window, create_star_button};
+use crate::completion::{Completion, SuggestionKind};
+use crate::downloads::create_downloads_button;
+use crate::find::{create_find_bar, FindBar};
+use crate::history::{history, Transition};
+use crate::pages::connect_downloads_page;
+use crate::network::{network_session, private_network_session};
+use crate::profiles::create_profiles_window;
+use crate::site_data::create_site_data_window;
+use crate::session::tab_state;
+use crate::site_settings::{apply_site_settings, attach_site_info, connect_site_settings, create_zoom_button};
+use crate::window::{is_private, window_for_widget, windows, BrowserWindow};
+use crate::search::{process_search_input, search_engines};
+use crate::setting::create_settings_window;
 
-pub fn create_tab(default_uri: &str, notebook: &Notebook, app: &Application) {
+/// A browser tab. Tabs are looked up by their page widget, which stays the
+/// same when the tab is reordered or moved to another window.
+#[derive(Debug)]
+pub struct Tab {
+    pub page: Box,
+    pub entry: Entry,
+    pub find_bar: FindBar,
+    pub webview: WebView,
+}
+
+thread_local! {
+    static TABS: RefCell<Vec<Rc<Tab>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Every open tab, in the order they were opened.
+pub fn tabs() -> Vec<Rc<Tab>> {
+    TABS.with(|t| t.borrow().clone())
+}
+
+pub fn tab_for_page(page: &Widget) -> Option<Rc<Tab>> {
+    tabs().into_iter().find(|tab| tab.page.upcast_ref::<Widget>() == page)
+}
+
+/// Drops the tabs of a notebook that is going away.
+pub fn forget_tabs(notebook: &Notebook) {
+    TABS.with(|t| t.borrow_mut().retain(|tab| notebook.page_num(&tab.page).is_none()));
+}
+
+impl Tab {
+    /// The window currently showing the tab.
+    pub fn window(&self) -> Option<Rc<BrowserWindow>> {
+        windows().into_iter().find(|bw| bw.notebook.page_num(&self.page).is_some())
+    }
+
+    pub fn select(&self) {
+        if let Some(browser_window) = self.window() {
+            let notebook = &browser_window.notebook;
+            notebook.set_current_page(notebook.page_num(&self.page));
+            browser_window.window.present();
+        }
+    }
+
+    /// Closes the tab, remembering it so it can be reopened.
+    pub fn close(&self) {
+        TABS.with(|t| t.borrow_mut().retain(|tab| tab.page != self.page));
+
+        let Some(browser_window) = self.window() else {
+            return;
+        };
+        let Some(index) = browser_window.notebook.page_num(&self.page) else {
+            return;
+        };
+
+        browser_window.remember_closed_tab(tab_state(&self.webview), index);
+        browser_window.notebook.remove_page(Some(index));
+    }
+}
+
+// Adds the favicon, loading spinner and audio indicator around `label` and
+// keeps them in sync with `webview`
+fn connect_tab_label(tab_box: &Box, label: &Label, webview: &WebView) {
+    let favicon = Image::new();
+    favicon.set_pixel_size(16);
+    favicon.set_visible(false);
+
+    let spinner = Spinner::new();
+    spinner.set_visible(false);
+
+    label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    label.set_max_width_chars(24);
+    label.set_width_chars(12);
+    label.set_xalign(0.0);
+    label.set_margin_start(4);
+    label.set_margin_end(4);
+
+    let audio = Button::with_label("🔊");
+    audio.add_css_class("flat");
+    audio.set_visible(false);
+
+    tab_box.prepend(&favicon);
+    tab_box.prepend(&spinner);
+    tab_box.insert_child_after(&audio, Some(label));
+
+    let label_clone = label.clone();
+    webview.connect_notify_local(Some("title"), move |webview, _| {
+        let title = webview
+            .title()
+            .filter(|t| !t.is_empty())
+            .or_else(|| webview.uri())
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| "New tab".to_string());
+        label_clone.set_text(&title);
+        label_clone.set_tooltip_text(Some(&title));
+    });
+
+    let favicon_clone = favicon.clone();
+    webview.connect_notify_local(Some("favicon"), move |webview, _| {
+        let texture = webview.favicon();
+        favicon_clone.set_paintable(texture.as_ref());
+        favicon_clone.set_visible(texture.is_some() && !webview.is_loading());
+    });
+
+    webview.connect_notify_local(Some("is-loading"), move |webview, _| {
+        let loading = webview.is_loading();
+        spinner.set_spinning(loading);
+        spinner.set_visible(loading);
+        favicon.set_visible(!loading && favicon.paintable().is_some());
+    });
+
+    let update_audio = {
+        let audio = audio.clone();
+        move |webview: &WebView| {
+            let muted = webview.is_muted();
+            audio.set_label(if muted { "🔇" } else { "🔊" });
+            audio.set_tooltip_text(Some(if muted { "Unmute tab" } else { "Mute tab" }));
+            audio.set_visible(muted || webview.is_playing_audio());
+        }
+    };
+    let update_clone = update_audio.clone();
+    webview.connect_notify_local(Some("is-playing-audio"), move |webview, _| update_clone(webview));
+    webview.connect_notify_local(Some("is-muted"), move |webview, _| update_audio(webview));
+
+    let webview_clone = webview.clone();
+    audio.connect_clicked(move |_| {
+        webview_clone.set_is_muted(!webview_clone.is_muted());
+    });
+}
+
+pub fn create_tab(default_uri: &str, notebook: &Notebook, app: &Application) -> Rc<Tab> {
     let tab_box = Box::new(gtk4::Orientation::Horizontal, 0);
-    let tab_label = Label::new(Some("New label"));
+    let tab_label = Label::new(Some("New tab"));
     let tab_close = Button::with_label("x");
 
     tab_box.append(&tab_label);
@@ -25,6 +170,15 @@ pub fn create_tab(default_uri: &str, notebook: &Notebook, app: &Application) {
     top_bar.append(&forward);
     top_bar.append(&refresh);
 
+    let private = is_private(notebook);
+    if private {
+        let badge = Label::new(Some("Private"));
+        badge.add_css_class("dim-label");
+        badge.set_margin_start(5);
+        badge.set_margin_end(5);
+        top_bar.append(&badge);
+    }
+
     let search_e = Entry::new();
     search_e.set_halign(gtk4::Align::Fill);
     search_e.set_hexpand(true);
@@ -32,30 +186,108 @@ pub fn create_tab(default_uri: &str, notebook: &Notebook, app: &Application) {
     top_bar.append(&search_e);
 
     let new = Button::with_label("+");
-    let settings = Button::with_label("⋮");
+    let menu = MenuButton::new();
+    menu.set_label("⋮");
 
-    top_bar.append(&new);
-    top_bar.append(&settings);
+    let menu_box = Box::new(gtk4::Orientation::Vertical, 0);
+    let history_btn = Button::with_label("History");
+    let bookmarks_btn = Button::with_label("Bookmarks");
+    let settings = Button::with_label("Settings");
+    let site_data_btn = Button::with_label("Site data");
+    let clear_data_btn = Button::with_label("Clear browsing data");
+    clear_data_btn.set_action_name(Some("win.clear-browsing-data"));
+    let profiles_btn = Button::with_label("Profiles");
+    let private_btn = Button::with_label("New private window");
+    private_btn.set_action_name(Some("win.new-private-window"));
 
-    let settings_rc = load_settings();
+    for button in [
+        &history_btn,
+        &bookmarks_btn,
+        &settings,
+        &site_data_btn,
+        &clear_data_btn,
+        &profiles_btn,
+        &private_btn,
+    ] {
+        button.add_css_class("flat");
+        menu_box.append(button);
+    }
 
-    // Create and configure WebView
-    let webview = WebView::new();
+    // Filled with the window's recently closed tabs whenever the menu opens
+    let closed_box = Box::new(gtk4::Orientation::Vertical, 0);
+    menu_box.append(&closed_box);
+
+    let menu_popover = Popover::new();
+    menu_popover.set_child(Some(&menu_box));
+    menu.set_popover(Some(&menu_popover));
 
-    let ses: NetworkSession = webview.network_session().expect("no network session");
+    let menu_clone = menu.clone();
+    menu_popover.connect_show(move |popover| {
+        while let Some(child) = closed_box.first_child() {
+            closed_box.remove(&child);
+        }
 
-    let cookie: CookieManager = ses.cookie_manager().expect("cookie manager not found");
+        let Some(browser_window) = window_for_widget(&menu_clone) else {
+            return;
+        };
+        if browser_window.closed_tabs.borrow().is_empty() {
+            return;
+        }
 
-    cookie.set_persistent_storage("rubra-cookies.sqlite", webkit6::CookiePersistentStorage::Sqlite);
+        closed_box.append(&Label::new(Some("Recently closed")));
 
-    webview.load_uri(default_uri);
+        for (n, closed) in browser_window.closed_tabs.borrow().iter().enumerate() {
+            let title = if closed.state.title.is_empty() { &closed.state.url } else { &closed.state.title };
+            let button = Button::with_label(title);
+            button.add_css_class("flat");
+            if let Some(label) = button.child().and_then(|c| c.downcast::<Label>().ok()) {
+                label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+                label.set_max_width_chars(40);
+            }
+
+            let browser_window = Rc::clone(&browser_window);
+            let popover = popover.clone();
+            button.connect_clicked(move |_| {
+                popover.popdown();
+                browser_window.reopen_closed_tab(n);
+            });
+            closed_box.append(&button);
+        }
+    });
+
+    top_bar.append(&new);
+    top_bar.append(&create_downloads_button());
+    top_bar.append(&menu);
+
+    // Create and configure WebView
+    let session = if private { private_network_session() } else { network_session() };
+    let webview = WebView::builder().network_session(&session).build();
 
     // Apply settings to the webview
-    apply_settings(&webview, &settings_rc.borrow());
+    apply_site_settings(&webview);
+    connect_content_blocker(&webview);
+    connect_site_settings(&webview);
+    connect_downloads_page(&webview);
+
+    webview.load_uri(default_uri);
 
     webview.set_vexpand(true);
 
+    connect_tab_label(&tab_box, &tab_label, &webview);
+
+    let zoom = create_zoom_button(&webview);
+    top_bar.insert_child_after(&zoom, Some(&search_e));
+    let star = create_star_button(&webview);
+    top_bar.insert_child_after(&star, Some(&zoom));
+    top_bar.insert_child_after(&create_blocker_button(&webview), Some(&star));
+
+    let bookmarks_bar = create_bookmarks_bar(&webview);
+
+    let find_bar = create_find_bar(&webview);
+
     hbox.append(&top_bar);
+    hbox.append(&bookmarks_bar);
+    hbox.append(&find_bar.bar);
     hbox.append(&webview);
 
     let webview_btn = webview.clone();
@@ -77,39 +309,160 @@ pub fn create_tab(default_uri: &str, notebook: &Notebook, app: &Application) {
         webview_btn.reload();
     });
 
-    let notebook_btn = notebook.clone();
+    new.set_action_name(Some("win.new-tab"));
+
     let app_clone = app.clone();
-    new.connect_clicked(move |_| {
-        create_tab("https://start.duckduckgo.com/", &notebook_btn, &app_clone);
+    let popover = menu_popover.clone();
+    settings.connect_clicked(move |_| {
+       popover.popdown();
+       create_settings_window(&app_clone);
     });
 
     let app_clone = app.clone();
-    let webview_btn = webview.clone();
-    settings.connect_clicked(move |_| {
-       create_settings_window(&app_clone, &webview_btn);
+    let popover = menu_popover.clone();
+    bookmarks_btn.connect_clicked(move |_| {
+        popover.popdown();
+        create_bookmarks_window(&app_clone);
+    });
+
+    let app_clone = app.clone();
+    let popover = menu_popover.clone();
+    site_data_btn.connect_clicked(move |_| {
+        popover.popdown();
+        create_site_data_window(&app_clone);
+    });
+
+    let app_clone = app.clone();
+    let popover = menu_popover.clone();
+    profiles_btn.connect_clicked(move |_| {
+        popover.popdown();
+        create_profiles_window(&app_clone);
     });
 
+    let app_clone = app.clone();
+    let popover = menu_popover.clone();
+    history_btn.connect_clicked(move |button| {
+        popover.popdown();
+        if let Some(browser_window) = window_for_widget(button) {
+            create_tab("rubra://history", &browser_window.notebook, &app_clone);
+        }
+    });
+
+    // How the next committed navigation was started, recorded into history
+    let transition = Rc::new(Cell::new(Transition::Other));
+
+    let completion = Completion::attach(&search_e, &webview);
+    attach_site_info(&search_e, &webview);
+
     let webview_btn = webview.clone();
+    let transition_clone = Rc::clone(&transition);
     search_e.connect_activate(move |entry| {
-        let q = entry.text();
+        let picked = completion.picked();
+        completion.hide();
+
+        match picked {
+            Some(suggestion) if suggestion.kind == SuggestionKind::OpenTab => {
+                if let Some(tab) = suggestion.tab {
+                    tab.select();
+                }
+                if let Some(uri) = webview_btn.uri() {
+                    entry.set_text(&uri);
+                }
+            }
+            Some(suggestion) => {
+                transition_clone.set(Transition::Typed);
+                webview_btn.load_uri(&suggestion.url);
+            }
+            None => {
+                let q = entry.text();
+
+                let processed_q = process_search_input(&q, &search_engines().borrow());
+
+                transition_clone.set(Transition::Typed);
+                webview_btn.load_uri(&processed_q);
+            }
+        }
+    });
+
+    let transition_clone = Rc::clone(&transition);
+    webview.connect_decide_policy(move |_, decision, decision_type| {
+        if decision_type == PolicyDecisionType::NavigationAction {
+            if let Some(mut action) = decision
+                .downcast_ref::<NavigationPolicyDecision>()
+                .and_then(|d| d.navigation_action())
+            {
+                let kind = match action.navigation_type() {
+                    NavigationType::LinkClicked => Transition::Link,
+                    NavigationType::FormSubmitted | NavigationType::FormResubmitted => Transition::FormSubmit,
+                    NavigationType::BackForward => Transition::BackForward,
+                    NavigationType::Reload => Transition::Reload,
+                    _ => Transition::Other,
+                };
 
-        let processed_q = process_search_input(&q);
+                // load_uri from the entry shows up as "other", keep it typed
+                if kind != Transition::Other {
+                    transition_clone.set(kind);
+                }
+            }
+        }
 
-        webview_btn.load_uri(&processed_q);
+        // Pages WebKit can't show are saved instead
+        if decision_type == PolicyDecisionType::Response {
+            if let Some(response) = decision.downcast_ref::<ResponsePolicyDecision>() {
+                if response.is_main_frame_main_resource() && !response.is_mime_type_supported() {
+                    decision.download();
+                    return true;
+                }
+            }
+        }
+        false
     });
 
+    // Private tabs leave no history
+    if !private {
+        let transition_clone = Rc::clone(&transition);
+        webview.connect_load_changed(move |webview, event| {
+            if event == LoadEvent::Committed {
+                if let Some(uri) = webview.uri() {
+                    history().borrow_mut().record_visit(&uri, transition_clone.replace(Transition::Other));
+                }
+            }
+        });
+
+        webview.connect_notify_local(Some("title"), move |webview, _| {
+            if let (Some(uri), Some(title)) = (webview.uri(), webview.title()) {
+                history().borrow_mut().set_title(&uri, &title);
+            }
+        });
+    }
+
+    let entry = search_e.clone();
     webview.connect_notify_local(Some("uri"), move |webview, _| {
         if let Some(uri) = webview.uri() {
-            search_e.set_text(&uri);
+            entry.set_text(&uri);
         }
     });
 
-    let index = notebook.append_page(&hbox, Some(&tab_box));
+    let tab = Rc::new(Tab {
+        page: hbox,
+        entry: search_e,
+        find_bar,
+        webview,
+    });
+    TABS.with(|t| t.borrow_mut().push(Rc::clone(&tab)));
+
+    let index = notebook.append_page(&tab.page, Some(&tab_box));
+    notebook.set_tab_reorderable(&tab.page, true);
+    notebook.set_tab_detachable(&tab.page, true);
 
-    let notebook_btn = notebook.clone();
+    let weak = Rc::downgrade(&tab);
     tab_close.connect_clicked(move |_| {
-        notebook_btn.remove_page(Some(index));
+        if let Some(tab) = weak.upgrade() {
+            tab.close();
+        }
     });
 
     notebook.set_current_page(Some(index));
+
+    tab
 }
@@ -0,0 +1,308 @@
+use gtk4::glib::{self, Propagation};
+use gtk4::{gdk, gio, prelude::*, Application, ApplicationWindow, CssProvider, Notebook, Settings, Widget};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use webkit6::prelude::*;
+use webkit6::WebView;
+
+use crate::clear_data::create_clear_data_dialog;
+use crate::network::end_private_session;
+use crate::paths::{profile, DEFAULT_PROFILE};
+use crate::session::{restore_tab_state, save_session, TabState};
+use crate::setting::home_page;
+use crate::site_settings::{next_zoom_level, set_site_zoom, site_settings};
+use crate::tab::{create_tab, forget_tabs, tab_for_page, Tab};
+
+const MAX_CLOSED_TABS: usize = 25;
+
+pub struct ClosedTab {
+    pub state: TabState,
+    /// Position of the tab in the notebook when it was closed.
+    pub index: u32,
+}
+
+pub struct BrowserWindow {
+    pub window: ApplicationWindow,
+    pub notebook: Notebook,
+    /// Private windows keep nothing once the last of them is closed.
+    pub private: bool,
+    /// Recently closed tabs, most recent first.
+    pub closed_tabs: RefCell<VecDeque<ClosedTab>>,
+}
+
+impl BrowserWindow {
+    pub fn remember_closed_tab(&self, state: TabState, index: u32) {
+        let mut closed_tabs = self.closed_tabs.borrow_mut();
+        closed_tabs.push_front(ClosedTab { state, index });
+        closed_tabs.truncate(MAX_CLOSED_TABS);
+    }
+
+    /// Reopens the `n`th most recently closed tab at its old position.
+    pub fn reopen_closed_tab(&self, n: usize) {
+        let Some(closed) = self.closed_tabs.borrow_mut().remove(n) else {
+            return;
+        };
+        let Some(app) = self.window.application() else {
+            return;
+        };
+
+        let tab = create_tab("about:blank", &self.notebook, &app);
+        self.notebook.reorder_child(&tab.page, Some(closed.index));
+        self.notebook.set_current_page(self.notebook.page_num(&tab.page));
+        restore_tab_state(&tab.webview, &closed.state);
+    }
+
+    /// The window's tabs, in notebook order.
+    pub fn tabs(&self) -> Vec<Rc<Tab>> {
+        (0..self.notebook.n_pages())
+            .filter_map(|i| self.notebook.nth_page(Some(i)))
+            .filter_map(|page| tab_for_page(&page))
+            .collect()
+    }
+
+    pub fn current_tab(&self) -> Option<Rc<Tab>> {
+        self.notebook
+            .nth_page(self.notebook.current_page())
+            .and_then(|page| tab_for_page(&page))
+    }
+
+    pub fn current_webview(&self) -> Option<WebView> {
+        self.current_tab().map(|tab| tab.webview.clone())
+    }
+
+    /// Switches `step` tabs to the right, wrapping around at either end.
+    pub fn cycle_tab(&self, step: i32) {
+        let count = self.notebook.n_pages() as i32;
+        if count == 0 {
+            return;
+        }
+
+        let current = self.notebook.current_page().unwrap_or(0) as i32;
+        self.notebook.set_current_page(Some((current + step).rem_euclid(count) as u32));
+    }
+
+    pub fn focus_address_bar(&self) {
+        if let Some(tab) = self.current_tab() {
+            tab.entry.grab_focus();
+            tab.entry.select_region(0, -1);
+        }
+    }
+
+    pub fn zoom(&self, zoom_in: bool) {
+        if let Some(webview) = self.current_webview() {
+            set_site_zoom(&webview, Some(next_zoom_level(webview.zoom_level(), zoom_in)));
+        }
+    }
+}
+
+fn add_action<F: Fn(&BrowserWindow) + 'static>(browser_window: &Rc<BrowserWindow>, name: &str, activate: F) {
+    let action = gio::SimpleAction::new(name, None);
+    let weak = Rc::downgrade(browser_window);
+    action.connect_activate(move |_, _| {
+        if let Some(browser_window) = weak.upgrade() {
+            activate(&browser_window);
+        }
+    });
+    browser_window.window.add_action(&action);
+}
+
+fn add_webview_action<F: Fn(&WebView) + 'static>(browser_window: &Rc<BrowserWindow>, name: &str, activate: F) {
+    add_action(browser_window, name, move |browser_window| {
+        if let Some(webview) = browser_window.current_webview() {
+            activate(&webview);
+        }
+    });
+}
+
+// Shortcuts for these are set up in keybindings.rs
+fn add_window_actions(browser_window: &Rc<BrowserWindow>) {
+    add_action(browser_window, "new-tab", |browser_window| {
+        if let Some(app) = browser_window.window.application() {
+            let home = home_page();
+            create_tab(&home, &browser_window.notebook, &app);
+        }
+    });
+    add_action(browser_window, "new-private-window", |browser_window| {
+        if let Some(app) = browser_window.window.application() {
+            let home = home_page();
+            create_tab(&home, &create_window(&app, true).notebook, &app);
+        }
+    });
+    add_action(browser_window, "clear-browsing-data", |browser_window| {
+        create_clear_data_dialog(&browser_window.window);
+    });
+    add_action(browser_window, "close-tab", |browser_window| {
+        if let Some(tab) = browser_window.current_tab() {
+            tab.close();
+        }
+    });
+    add_action(browser_window, "reopen-closed-tab", |browser_window| browser_window.reopen_closed_tab(0));
+    add_action(browser_window, "next-tab", |browser_window| browser_window.cycle_tab(1));
+    add_action(browser_window, "previous-tab", |browser_window| browser_window.cycle_tab(-1));
+    add_action(browser_window, "focus-address-bar", |browser_window| browser_window.focus_address_bar());
+    add_action(browser_window, "find", |browser_window| {
+        if let Some(tab) = browser_window.current_tab() {
+            tab.find_bar.show();
+        }
+    });
+    add_action(browser_window, "zoom-in", |browser_window| browser_window.zoom(true));
+    add_action(browser_window, "zoom-out", |browser_window| browser_window.zoom(false));
+
+    add_webview_action(browser_window, "zoom-reset", |webview| set_site_zoom(webview, None));
+    add_webview_action(browser_window, "reload", |webview| webview.reload());
+    add_webview_action(browser_window, "hard-reload", |webview| webview.reload_bypass_cache());
+    add_webview_action(browser_window, "back", |webview| {
+        if webview.can_go_back() {
+            webview.go_back();
+        }
+    });
+    add_webview_action(browser_window, "forward", |webview| {
+        if webview.can_go_forward() {
+            webview.go_forward();
+        }
+    });
+}
+
+thread_local! {
+    static WINDOWS: RefCell<Vec<Rc<BrowserWindow>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Every open browser window, in the order they were opened.
+pub fn windows() -> Vec<Rc<BrowserWindow>> {
+    WINDOWS.with(|w| w.borrow().clone())
+}
+
+/// The browser window containing `widget`.
+pub fn window_for_widget(widget: &impl IsA<Widget>) -> Option<Rc<BrowserWindow>> {
+    let root = widget.root()?;
+    windows()
+        .into_iter()
+        .find(|bw| bw.window.upcast_ref::<gtk4::Root>() == &root)
+}
+
+/// Whether `widget` is in a private window.
+pub fn is_private(widget: &impl IsA<Widget>) -> bool {
+    window_for_widget(widget).is_some_and(|bw| bw.private)
+}
+
+const PRIVATE_STYLE: &str = "
+window.private notebook > header {
+    background-color: #3d2b5c;
+}
+";
+
+thread_local! {
+    static PRIVATE_STYLE_LOADED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn load_private_style() {
+    if PRIVATE_STYLE_LOADED.with(|l| l.replace(true)) {
+        return;
+    }
+    let Some(display) = gdk::Display::default() else {
+        return;
+    };
+
+    let provider = CssProvider::new();
+    provider.load_from_string(PRIVATE_STYLE);
+    gtk4::style_context_add_provider_for_display(&display, &provider, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION);
+}
+
+/// Creates an empty browser window; callers add its tabs.
+pub fn create_window(app: &Application, private: bool) -> Rc<BrowserWindow> {
+    let settings: Settings = Settings::default().unwrap();
+
+    settings.set_gtk_application_prefer_dark_theme(true);
+
+    let mut title = match profile().as_str() {
+        DEFAULT_PROFILE => "aapelix/rubra".to_string(),
+        name => format!("aapelix/rubra ({})", name),
+    };
+    if private {
+        title.push_str(" (private)");
+    }
+
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .default_width(1500)
+        .default_height(900)
+        .title(title)
+        .resizable(true)
+        .build();
+
+    if private {
+        load_private_style();
+        window.add_css_class("private");
+    }
+
+    let notebook = Notebook::new();
+    notebook.set_scrollable(true);
+    // Tabs can be dragged between all browser windows of the same kind, as
+    // private tabs can't change their network session
+    notebook.set_group_name(Some(if private { "rubra-private-tabs" } else { "rubra-tabs" }));
+
+    window.set_child(Some(&notebook));
+
+    let browser_window = Rc::new(BrowserWindow {
+        window,
+        notebook,
+        private,
+        closed_tabs: RefCell::new(VecDeque::new()),
+    });
+    WINDOWS.with(|w| w.borrow_mut().push(Rc::clone(&browser_window)));
+
+    add_window_actions(&browser_window);
+
+    // A tab dropped outside of every window gets a window of its own
+    browser_window.notebook.connect_create_window(move |notebook, _| {
+        let app = notebook.root()?.downcast::<ApplicationWindow>().ok()?.application()?;
+        Some(create_window(&app, private).notebook.clone())
+    });
+
+    // Closing or dragging away the last tab closes the window. Deferred so
+    // the notebook isn't torn down while it is still removing the page.
+    let window = browser_window.window.downgrade();
+    browser_window.notebook.connect_page_removed(move |notebook, _, _| {
+        if notebook.n_pages() > 0 {
+            return;
+        }
+
+        let window = window.clone();
+        let notebook = notebook.clone();
+        glib::idle_add_local_once(move || {
+            if let Some(window) = window.upgrade() {
+                if notebook.n_pages() == 0 {
+                    window.close();
+                }
+            }
+        });
+    });
+
+    // The last window is kept in the session, so that closing it and
+    // quitting restores it next time. Private windows are never saved.
+    browser_window.window.connect_close_request(move |window| {
+        if private || windows().iter().filter(|bw| !bw.private).count() > 1 {
+            WINDOWS.with(|w| w.borrow_mut().retain(|bw| bw.window != *window));
+        }
+        if !private {
+            save_session();
+        }
+        Propagation::Proceed
+    });
+
+    let notebook = browser_window.notebook.clone();
+    browser_window.window.connect_destroy(move |window| {
+        forget_tabs(&notebook);
+        WINDOWS.with(|w| w.borrow_mut().retain(|bw| bw.window != *window));
+
+        if private && !windows().iter().any(|bw| bw.private) {
+            end_private_session();
+            site_settings().borrow_mut().forget_private();
+        }
+    });
+
+    browser_window.window.present();
+
+    browser_window
+}